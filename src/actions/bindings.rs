@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use bevy::prelude::*;
+use leafwing_input_manager::{prelude::*, user_input::InputKind};
+use serde::{Deserialize, Serialize};
+
+use super::Action;
+
+/// User bindings file, next to the executable's working directory
+pub const BINDINGS_PATH: &str = "bindings.ron";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Device {
+    KeyboardMouse,
+    Gamepad,
+}
+
+/// Where an action is read, actions only conflict with actions read at the same time
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BindingContext {
+    Gameplay,
+    Camera,
+    /// Only while in photo mode, the game is paused
+    Photo,
+    Editor,
+}
+
+impl BindingContext {
+    pub const ALL: [BindingContext; 4] = [BindingContext::Gameplay, BindingContext::Camera, BindingContext::Photo, BindingContext::Editor];
+
+    /// Whether actions of both contexts are read in the same frames
+    pub fn overlaps(self, other: Self) -> bool {
+        use BindingContext::*;
+        // The camera is steered while aiming, and switches in and out of photo mode
+        self == other || matches!(
+            (self, other),
+            (Gameplay, Camera) | (Camera, Gameplay) | (Photo, Camera) | (Camera, Photo)
+        )
+    }
+
+    /// Keys the context reads directly, not through an action
+    pub fn reserved_keys(self) -> &'static [KeyCode] {
+        match self {
+            BindingContext::Gameplay | BindingContext::Photo => &[],
+            // Frees the mouse in free flight
+            BindingContext::Camera => &[KeyCode::Escape],
+            // Gizmo modes, focusing the selection, deleting it, Ctrl+Z/Ctrl+Y undo and redo
+            // and cancelling surface placement
+            BindingContext::Editor => &[KeyCode::W, KeyCode::E, KeyCode::R, KeyCode::F, KeyCode::Delete, KeyCode::Z, KeyCode::Y, KeyCode::Escape],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Binding {
+    /// A key or button, or a whole stick for axis actions
+    Single(InputKind),
+    /// Four buttons making up an axis action
+    DPad { up: InputKind, down: InputKind, left: InputKind, right: InputKind },
+}
+
+impl Binding {
+    fn keys(up: KeyCode, down: KeyCode, left: KeyCode, right: KeyCode) -> Self {
+        Binding::DPad { up: up.into(), down: down.into(), left: left.into(), right: right.into() }
+    }
+
+    /// Every raw input this binding listens to
+    pub fn inputs(&self) -> Vec<InputKind> {
+        match self {
+            Binding::Single(input) => vec![*input],
+            Binding::DPad { up, down, left, right } => vec![*up, *down, *left, *right],
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Single(input) => input_label(input),
+            Binding::DPad { up, down, left, right } => [up, down, left, right]
+                .map(input_label)
+                .join(" "),
+        }
+    }
+
+    fn user_input(&self) -> UserInput {
+        match self {
+            Binding::Single(input) => UserInput::from(*input),
+            Binding::DPad { up, down, left, right } => UserInput::from(VirtualDPad {
+                up: *up,
+                down: *down,
+                left: *left,
+                right: *right,
+            }),
+        }
+    }
+}
+
+fn input_label(input: &InputKind) -> String {
+    match input {
+        InputKind::Keyboard(key) => format!("{key:?}"),
+        InputKind::Mouse(button) => format!("Mouse {button:?}"),
+        InputKind::GamepadButton(button) => format!("{button:?}"),
+        InputKind::DualAxis(axis) if *axis == DualAxis::left_stick() => "Left stick".to_string(),
+        InputKind::DualAxis(axis) if *axis == DualAxis::right_stick() => "Right stick".to_string(),
+        InputKind::DualAxis(axis) if *axis == DualAxis::mouse_motion() => "Mouse motion".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Input bindings of every action, one per device, and how camera input is flipped
+///
+/// Entities get their `InputMap` built from this through `BoundActions`.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Bindings {
+    pub actions: BTreeMap<(Action, Device), Binding>,
+    #[serde(default)]
+    pub invert_x: bool,
+    #[serde(default)]
+    pub invert_y: bool,
+}
+
+/// `BINDINGS_PATH` as saved before it held the invert toggles
+#[derive(Deserialize)]
+struct OldBindings(BTreeMap<(Action, Device), Binding>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use KeyCode::*;
+        use GamepadButtonType::*;
+        let actions = BTreeMap::from([
+            ((Action::Aim, Device::KeyboardMouse), Binding::keys(Up, Down, Left, Right)),
+            ((Action::Aim, Device::Gamepad), Binding::Single(DualAxis::left_stick().into())),
+            ((Action::RotateCamera, Device::KeyboardMouse), Binding::keys(W, S, A, D)),
+            ((Action::RotateCamera, Device::Gamepad), Binding::Single(DualAxis::right_stick().into())),
+            ((Action::Shoot, Device::KeyboardMouse), Binding::Single(Space.into())),
+            ((Action::Shoot, Device::Gamepad), Binding::Single(South.into())),
+            ((Action::CancelShot, Device::KeyboardMouse), Binding::Single(Back.into())),
+            ((Action::CancelShot, Device::Gamepad), Binding::Single(East.into())),
+            ((Action::Spin, Device::KeyboardMouse), Binding::keys(I, K, J, L)),
+            ((Action::Spin, Device::Gamepad), Binding::DPad {
+                up: DPadUp.into(),
+                down: DPadDown.into(),
+                left: DPadLeft.into(),
+                right: DPadRight.into(),
+            }),
+            ((Action::Orbit, Device::KeyboardMouse), Binding::Single(MouseButton::Right.into())),
+            ((Action::CycleCamera, Device::KeyboardMouse), Binding::Single(C.into())),
+            ((Action::CycleCamera, Device::Gamepad), Binding::Single(North.into())),
+            ((Action::ToggleMinimap, Device::KeyboardMouse), Binding::Single(M.into())),
+            ((Action::ToggleMinimap, Device::Gamepad), Binding::Single(Select.into())),
+            ((Action::MinimapZoom, Device::KeyboardMouse), Binding::Single(N.into())),
+            ((Action::MinimapZoom, Device::Gamepad), Binding::Single(RightThumb.into())),
+            ((Action::PhotoMode, Device::KeyboardMouse), Binding::Single(P.into())),
+            ((Action::PhotoMode, Device::Gamepad), Binding::Single(Start.into())),
+            ((Action::TakePhoto, Device::KeyboardMouse), Binding::Single(Return.into())),
+            ((Action::TakePhoto, Device::Gamepad), Binding::Single(West.into())),
+            ((Action::PhotoLens, Device::KeyboardMouse), Binding::keys(R, F, Q, E)),
+            ((Action::PhotoLens, Device::Gamepad), Binding::DPad {
+                up: RightTrigger.into(),
+                down: LeftTrigger.into(),
+                left: LeftTrigger2.into(),
+                right: RightTrigger2.into(),
+            }),
+            ((Action::PhotoBrighter, Device::KeyboardMouse), Binding::Single(Equals.into())),
+            ((Action::PhotoDarker, Device::KeyboardMouse), Binding::Single(Minus.into())),
+            ((Action::PhotoBloom, Device::KeyboardMouse), Binding::Single(B.into())),
+            ((Action::PhotoBloom, Device::Gamepad), Binding::Single(LeftThumb.into())),
+            ((Action::ToggleEditor, Device::KeyboardMouse), Binding::Single(F1.into())),
+        ]);
+        Self { actions, invert_x: false, invert_y: false }
+    }
+}
+
+impl Bindings {
+    /// User bindings from `BINDINGS_PATH` over the defaults, so actions added since the file was saved still get bound
+    pub fn load() -> Self {
+        let mut bindings = Self::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        match std::fs::read_to_string(BINDINGS_PATH) {
+            Ok(file) => match ron::from_str::<Bindings>(&file) {
+                Ok(user) => {
+                    bindings.actions.extend(user.actions);
+                    bindings.invert_x = user.invert_x;
+                    bindings.invert_y = user.invert_y;
+                },
+                Err(err) => match ron::from_str::<OldBindings>(&file) {
+                    Ok(old) => bindings.actions.extend(old.0),
+                    Err(_) => warn!("Invalid bindings file, using defaults: {err}"),
+                },
+            },
+            Err(_) => info!("No bindings file, using defaults"),
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        // This can't work in WASM as there is no filesystem access
+        #[cfg(not(target_arch = "wasm32"))]
+        match ron::ser::to_string_pretty(self, default()) {
+            Ok(serialized) => {
+                if let Err(err) = std::fs::write(BINDINGS_PATH, serialized) {
+                    error!("Could not save bindings: {err}");
+                }
+            },
+            Err(err) => error!("Could not serialize bindings: {err}"),
+        }
+    }
+
+    pub fn get(&self, action: Action, device: Device) -> Option<&Binding> {
+        self.actions.get(&(action, device))
+    }
+
+    pub fn set(&mut self, action: Action, device: Device, binding: Binding) {
+        self.actions.insert((action, device), binding);
+    }
+
+    pub fn input_map(&self, actions: &[Action]) -> InputMap<Action> {
+        let mut input_map = InputMap::default();
+        for ((action, _), binding) in self.actions.iter().filter(|((action, _), _)| actions.contains(action)) {
+            input_map.insert(binding.user_input(), *action);
+        }
+        input_map
+    }
+
+    /// Pairs of different actions sharing an input on the same device in overlapping contexts
+    pub fn conflicts(&self) -> Vec<(Action, Action, Device)> {
+        let mut conflicts = Vec::new();
+        let entries: Vec<_> = self.actions.iter().collect();
+        for (i, ((action_a, device_a), binding_a)) in entries.iter().enumerate() {
+            for ((action_b, device_b), binding_b) in entries.iter().skip(i + 1) {
+                if action_a == action_b || device_a != device_b || !action_a.overlaps(*action_b) {
+                    continue;
+                }
+                let inputs_b = binding_b.inputs();
+                if binding_a.inputs().iter().any(|input| inputs_b.contains(input)) {
+                    conflicts.push((*action_a, *action_b, *device_a));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Actions bound to a key that an overlapping context reserves
+    pub fn reserved_conflicts(&self) -> Vec<(Action, KeyCode)> {
+        let mut conflicts = Vec::new();
+        for ((action, _), binding) in self.actions.iter() {
+            for input in binding.inputs() {
+                let InputKind::Keyboard(key) = input else { continue };
+                let reserved = BindingContext::ALL.into_iter()
+                    .filter(|context| action.contexts().iter().any(|own| own.overlaps(*context)))
+                    .any(|context| context.reserved_keys().contains(&key));
+                if reserved {
+                    conflicts.push((*action, key));
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn is_conflicted(&self, action: Action, device: Device) -> bool {
+        self.conflicts().iter().any(|(a, b, d)| *d == device && (*a == action || *b == action))
+            || device == Device::KeyboardMouse && self.reserved_conflicts().iter().any(|(a, _)| *a == action)
+    }
+}
+
+/// Actions an entity's `InputMap` is built with, kept in sync with `Bindings`
+#[derive(Component, Clone)]
+pub struct BoundActions(pub Vec<Action>);
+
+impl BoundActions {
+    pub fn input_manager_bundle(&self, bindings: &Bindings) -> InputManagerBundle<Action> {
+        InputManagerBundle {
+            action_state: ActionState::default(),
+            input_map: bindings.input_map(&self.0),
+        }
+    }
+}
+
+pub(super) fn apply_bindings(
+    bindings: Res<Bindings>,
+    mut query: Query<(&BoundActions, &mut InputMap<Action>)>,
+) {
+    if !bindings.is_changed() {
+        return;
+    }
+    for (bound, mut input_map) in query.iter_mut() {
+        *input_map = bindings.input_map(&bound.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        assert!(Bindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn shared_input_conflicts_on_the_same_device() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::CancelShot, Device::KeyboardMouse, Binding::Single(KeyCode::Space.into()));
+        assert_eq!(bindings.conflicts(), vec![(Action::Shoot, Action::CancelShot, Device::KeyboardMouse)]);
+        assert!(bindings.is_conflicted(Action::Shoot, Device::KeyboardMouse));
+        assert!(!bindings.is_conflicted(Action::Shoot, Device::Gamepad));
+        assert!(!bindings.is_conflicted(Action::Aim, Device::KeyboardMouse));
+    }
+
+    #[test]
+    fn reserved_key_conflicts() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::CancelShot, Device::KeyboardMouse, Binding::Single(KeyCode::Escape.into()));
+        assert!(bindings.conflicts().is_empty());
+        assert_eq!(bindings.reserved_conflicts(), vec![(Action::CancelShot, KeyCode::Escape)]);
+        assert!(bindings.is_conflicted(Action::CancelShot, Device::KeyboardMouse));
+        assert!(!bindings.is_conflicted(Action::CancelShot, Device::Gamepad));
+    }
+
+    #[test]
+    fn contexts_that_are_never_read_together_can_share_inputs() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::TakePhoto, Device::KeyboardMouse, Binding::Single(KeyCode::Space.into()));
+        assert!(bindings.conflicts().is_empty());
+        bindings.set(Action::TakePhoto, Device::KeyboardMouse, Binding::Single(KeyCode::C.into()));
+        assert_eq!(bindings.conflicts(), vec![(Action::CycleCamera, Action::TakePhoto, Device::KeyboardMouse)]);
+    }
+
+    #[test]
+    fn editor_keys_are_only_reserved_in_the_editor() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Shoot, Device::KeyboardMouse, Binding::Single(KeyCode::R.into()));
+        assert!(bindings.reserved_conflicts().is_empty());
+        bindings.set(Action::ToggleEditor, Device::KeyboardMouse, Binding::Single(KeyCode::F.into()));
+        assert_eq!(bindings.reserved_conflicts(), vec![(Action::ToggleEditor, KeyCode::F)]);
+        bindings.set(Action::ToggleEditor, Device::KeyboardMouse, Binding::Single(KeyCode::Z.into()));
+        assert_eq!(bindings.reserved_conflicts(), vec![(Action::ToggleEditor, KeyCode::Z)]);
+    }
+
+    #[test]
+    fn dpad_conflicts_with_any_of_its_buttons() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Shoot, Device::KeyboardMouse, Binding::Single(KeyCode::A.into()));
+        assert_eq!(bindings.conflicts(), vec![(Action::RotateCamera, Action::Shoot, Device::KeyboardMouse)]);
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Shoot, Device::KeyboardMouse, Binding::Single(MouseButton::Left.into()));
+        bindings.invert_y = true;
+        let serialized = ron::ser::to_string_pretty(&bindings, default()).unwrap();
+        assert_eq!(ron::from_str::<Bindings>(&serialized).unwrap(), bindings);
+    }
+
+    #[test]
+    fn old_file_without_inverts_parses() {
+        let serialized = ron::ser::to_string(&Bindings::default().actions).unwrap();
+        let old = ron::from_str::<OldBindings>(&format!("({serialized})")).unwrap();
+        assert_eq!(old.0, Bindings::default().actions);
+    }
+}
@@ -1,17 +1,92 @@
+mod bindings;
+
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub use bindings::{Binding, BindingContext, Bindings, BoundActions, Device, BINDINGS_PATH};
 
 pub struct ActionsPlugin;
 
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(InputManagerPlugin::<Action>::default());
+        app.add_plugin(InputManagerPlugin::<Action>::default())
+            .insert_resource(Bindings::load())
+            .add_system(bindings::apply_bindings);
     }
 }
 
-#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
+#[derive(Actionlike, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Debug)]
 pub enum Action {
     Aim,
     RotateCamera,
     Shoot,
-}
\ No newline at end of file
+    CancelShot,
+    Spin,
+    /// Held to orbit the camera with the mouse
+    Orbit,
+    CycleCamera,
+    ToggleMinimap,
+    MinimapZoom,
+    PhotoMode,
+    TakePhoto,
+    /// Roll on X, zoom on Y
+    PhotoLens,
+    PhotoBrighter,
+    PhotoDarker,
+    PhotoBloom,
+    ToggleEditor,
+}
+
+impl Action {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Aim => "Aim",
+            Action::RotateCamera => "Rotate camera",
+            Action::Shoot => "Shoot",
+            Action::CancelShot => "Cancel shot",
+            Action::Spin => "Spin",
+            Action::Orbit => "Orbit camera",
+            Action::CycleCamera => "Camera mode",
+            Action::ToggleMinimap => "Minimap",
+            Action::MinimapZoom => "Minimap zoom",
+            Action::PhotoMode => "Photo mode",
+            Action::TakePhoto => "Take photo",
+            Action::PhotoLens => "Photo roll and zoom",
+            Action::PhotoBrighter => "Photo brighter",
+            Action::PhotoDarker => "Photo darker",
+            Action::PhotoBloom => "Photo bloom",
+            Action::ToggleEditor => "Level editor",
+        }
+    }
+
+    /// Where the action is read, most only in one place
+    pub fn contexts(&self) -> &'static [BindingContext] {
+        match self {
+            Action::Aim | Action::Shoot | Action::CancelShot | Action::Spin => &[BindingContext::Gameplay],
+            Action::RotateCamera
+            | Action::Orbit
+            | Action::CycleCamera
+            | Action::ToggleMinimap
+            | Action::MinimapZoom
+            | Action::PhotoMode => &[BindingContext::Camera],
+            Action::TakePhoto
+            | Action::PhotoLens
+            | Action::PhotoBrighter
+            | Action::PhotoDarker
+            | Action::PhotoBloom => &[BindingContext::Photo],
+            // Opens the editor while playing and closes it again
+            Action::ToggleEditor => &[BindingContext::Camera, BindingContext::Editor],
+        }
+    }
+
+    /// Whether both actions can be read in the same frame
+    pub fn overlaps(&self, other: Action) -> bool {
+        self.contexts().iter().any(|context| other.contexts().iter().any(|other| context.overlaps(*other)))
+    }
+
+    /// Two axis actions, bound to a stick or four buttons
+    pub fn is_axis(&self) -> bool {
+        matches!(self, Action::Aim | Action::RotateCamera | Action::Spin | Action::PhotoLens)
+    }
+}
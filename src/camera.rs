@@ -1,35 +1,57 @@
 use std::f32::consts::PI;
 
-use bevy::{prelude::*, core_pipeline::bloom::BloomSettings};
-use leafwing_input_manager::{prelude::{ActionState, InputMap, DualAxis, VirtualDPad}, InputManagerBundle};
+use bevy::{prelude::*, core_pipeline::bloom::BloomSettings, input::mouse::{MouseMotion, MouseWheel, MouseScrollUnit}};
+use bevy_rapier3d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use smooth_bevy_cameras::{LookTransform, LookTransformBundle, LookTransformPlugin, Smoother, look_transform_system};
 
-use crate::{AppState, game::gameplay_elements::launcher::Launcher, actions::Action};
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+use crate::{AppState, game::{gameplay_elements::launcher::Launcher, level::{Level, intro_playing}}, actions::{Action, Bindings, BoundActions}, environment::physics::{RenderInterpolation, interpolation_alpha}};
+use crate::photo::in_photo_mode;
+use crate::player::{FlyCam, NoCameraPlayerPlugin, InputState as FlyInput};
 
 pub struct InternalCameraPlugin;
 
 impl Plugin for InternalCameraPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<CameraSettings>()
+            .add_plugin(NoCameraPlayerPlugin)
+            .add_plugin(LookTransformPlugin)
             .add_system(setup.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(cleanup_camera.in_schedule(OnExit(AppState::Playing)))
+            .add_system(cycle_camera_mode
+                .in_set(OnUpdate(AppState::Playing))
+                .before(aim_camera)
+                .run_if(not(intro_playing))
+                .run_if(not(in_photo_mode))
+            )
             .add_system(aim_camera
                 .in_set(OnUpdate(AppState::Playing))
+                .before(look_transform_system)
+            )
+            .add_system(avoid_obstructions
+                .in_set(OnUpdate(AppState::Playing))
+                .after(look_transform_system)
+            )
+            .add_system(overview_camera
+                .in_set(OnUpdate(AppState::Playing))
+            )
+            .add_system(blend_camera_transition
+                .in_set(OnUpdate(AppState::Playing))
+                .after(avoid_obstructions)
+                .after(overview_camera)
             )
             .add_system(reset_focus
                 .in_set(OnUpdate(AppState::Playing))
             )
-            // .add_plugin(LookTransformPlugin)
-            // .add_plugin(NoCameraPlayerPlugin)
-            // .insert_resource(FreeCam(false))
             // .add_system(ball_follow_camera
             //     .in_set(OnUpdate(AppState::Playing))
-            //     .run_if(not(free_cam))
             // )
             // .add_system(reset_camera
             //     .in_set(OnUpdate(AppState::Playing))
             //     .in_set(OnUpdate(GameState::InProgress))
-            //     .run_if(not(free_cam))
             // )
-            // .add_system(toggle_freecam_stuff)
             ;
     }
 }
@@ -37,31 +59,324 @@ impl Plugin for InternalCameraPlugin {
 #[derive(Component)]
 pub struct Focus;
 
+#[derive(Resource)]
+pub struct CameraSettings {
+    /// Stick and key rotation in radians per second
+    pub rotate_speed: f32,
+    /// Mouse orbit in radians per pixel
+    pub orbit_sensitivity: f32,
+    /// Fraction of the distance each scroll wheel line zooms
+    pub zoom_step: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// How fast the camera moves back out once an obstacle is gone, per second
+    pub arm_return_rate: f32,
+    /// How tightly the camera follows its focus, higher is snappier
+    pub follow_stiffness: f32,
+    /// Follow stiffness right after the focus changed, e.g. from the launcher to the ball
+    pub handoff_stiffness: f32,
+    pub handoff_secs: f32,
+    /// Seconds of ball velocity the camera looks ahead
+    pub look_ahead_secs: f32,
+    pub max_look_ahead: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            rotate_speed: 1.2,
+            orbit_sensitivity: 0.005,
+            zoom_step: 0.1,
+            min_distance: 4.0,
+            max_distance: 40.0,
+            arm_return_rate: 4.0,
+            follow_stiffness: 12.0,
+            handoff_stiffness: 3.0,
+            handoff_secs: 0.8,
+            look_ahead_secs: 0.3,
+            max_look_ahead: 8.0,
+        }
+    }
+}
+
+/// Angles and distance of the camera around its focus
+#[derive(Component)]
+pub struct OrbitCamera {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Wanted distance, set by zooming
+    pub distance: f32,
+    /// Actual distance, shorter while something is between the focus and the camera
+    pub arm_length: f32,
+    focus: Option<Entity>,
+    /// Seconds left of the softer follow after the focus changed
+    handoff_left: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self { yaw: 0.0, pitch: 0.0, distance: 10.0, arm_length: 10.0, focus: None, handoff_left: 0.0 }
+    }
+}
+
+fn follow_bundle() -> LookTransformBundle {
+    LookTransformBundle {
+        transform: LookTransform::new(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y),
+        smoother: Smoother::new(0.0),
+    }
+}
+
+impl OrbitCamera {
+    /// Turns by `delta` radians, yaw wraps around to stay within -PI..PI and pitch stops at straight up or down
+    pub fn turn(&mut self, delta: Vec2) {
+        self.yaw = (self.yaw - delta.x + PI).rem_euclid(2.0 * PI) - PI;
+        self.pitch = (self.pitch + delta.y).clamp(-PI/2., PI/2.);
+    }
+}
+
+/// Radius of the sphere cast from the focus, keeps the near plane out of walls
+const CAMERA_RADIUS: f32 = 0.3;
+
+/// Distance from `focus` along `direction` the camera can go without ending up in level geometry
+fn spring_arm_length(rapier_context: &RapierContext, focus: Vec3, direction: Vec3, max_length: f32) -> f32 {
+    // Only static geometry blocks the view, not the ball or sensors like the goal and death zone
+    let filter = QueryFilter::exclude_dynamic().exclude_sensors();
+    rapier_context
+        .cast_shape(focus, Quat::IDENTITY, direction, &Collider::ball(CAMERA_RADIUS), max_length, filter)
+        .map_or(max_length, |(_, hit)| hit.toi)
+}
+
+/// What the main camera is doing, cycled with `Action::CycleCamera`
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CameraMode {
+    /// Orbit the ball or launcher
+    #[default]
+    Follow,
+    /// Look down on the whole level
+    Overview,
+    /// Fly around freely with the mouse and WASD
+    FreeFly,
+}
+
+impl CameraMode {
+    fn next(&self) -> Self {
+        match self {
+            CameraMode::Follow => CameraMode::Overview,
+            CameraMode::Overview => CameraMode::FreeFly,
+            CameraMode::FreeFly => CameraMode::Follow,
+        }
+    }
+}
+
+/// Run condition, gameplay input is ignored while flying around
+pub fn free_flying(query: Query<&CameraMode, With<MainCamera>>) -> bool {
+    query.get_single().map_or(false, |mode| *mode == CameraMode::FreeFly)
+}
+
+/// Eases the camera from where it was into the new mode
+#[derive(Component)]
+pub(crate) struct CameraTransition {
+    from: Transform,
+    timer: Timer,
+}
+
+const TRANSITION_SECS: f32 = 0.6;
+
+impl CameraTransition {
+    pub(crate) fn new(from: Transform) -> Self {
+        Self { from, timer: Timer::from_seconds(TRANSITION_SECS, TimerMode::Once) }
+    }
+}
+
+fn cycle_camera_mode(
+    mut commands: Commands,
+    mut camera_query: Query<(Entity, &Transform, &mut CameraMode, &ActionState<Action>), With<MainCamera>>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut fly_input: ResMut<FlyInput>,
+) {
+    let Ok((camera, transform, mut mode, action_state)) = camera_query.get_single_mut() else { return };
+    if !action_state.just_pressed(Action::CycleCamera) {
+        return;
+    }
+    let next = mode.next();
+    switch_camera_mode(&mut commands, camera, transform, &mut mode, next, q_window.get_single_mut().ok(), &mut fly_input);
+}
+
+/// Puts the main camera in `next` mode, adding and removing what drives each mode
+pub(crate) fn switch_camera_mode(
+    commands: &mut Commands,
+    camera: Entity,
+    transform: &Transform,
+    mode: &mut CameraMode,
+    next: CameraMode,
+    window: Option<Mut<Window>>,
+    fly_input: &mut FlyInput,
+) {
+    let previous = *mode;
+    if previous == next {
+        return;
+    }
+    *mode = next;
+    info!("Camera mode {:?}", *mode);
+
+    if *mode == CameraMode::FreeFly {
+        // Fly on from the current view
+        fly_input.look_from(transform);
+        commands.entity(camera).insert(FlyCam).remove::<CameraTransition>();
+    }
+    if previous == CameraMode::FreeFly {
+        commands.entity(camera).remove::<FlyCam>();
+        if let Some(mut window) = window {
+            window.cursor.grab_mode = CursorGrabMode::None;
+            window.cursor.visible = true;
+        }
+    }
+    // The look transform would keep overwriting the other modes
+    if previous == CameraMode::Follow {
+        commands.entity(camera).remove::<(LookTransform, Smoother)>();
+    }
+    if *mode == CameraMode::Follow {
+        commands.entity(camera).insert(follow_bundle());
+    }
+    if *mode != CameraMode::FreeFly {
+        commands.entity(camera).insert(CameraTransition::new(*transform));
+    }
+}
+
+// Sets where the camera wants to be, the look transform smoother then damps it there
+#[allow(clippy::too_many_arguments)]
 fn aim_camera(
-    mut camera_query: Query<(&mut Transform, &ActionState<Action>), (With<MainCamera>, Without<Focus>)>,
-    focus_query: Query<&Transform, (With<Focus>, Without<MainCamera>)>,
-    mut rotation: Local<Vec2>,
+    mut camera_query: Query<(&mut LookTransform, &mut Smoother, &mut OrbitCamera, &ActionState<Action>, &CameraMode), (With<MainCamera>, Without<Focus>)>,
+    focus_query: Query<(Entity, &Transform, Option<&RenderInterpolation>, Option<&Velocity>), (With<Focus>, Without<MainCamera>)>,
+    settings: Res<CameraSettings>,
+    bindings: Res<Bindings>,
+    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
 ) {
-    let sensitivity = 0.02;
-    let camera_dist = 10.0;
+    let Ok((mut look, mut smoother, mut orbit, action_state, mode)) = camera_query.get_single_mut() else { return };
+    if *mode != CameraMode::Follow {
+        return;
+    }
 
-    if let Ok((mut trans, action_state)) = camera_query.get_single_mut() {
-        for focus_trans in focus_query.iter() {
-            // info!("{:?}", rotation);
-            let axis_pair = action_state.clamped_axis_pair(Action::RotateCamera).unwrap();
-    
-            rotation.x = sensitivity * -axis_pair.x() + rotation.x;
-            rotation.y = (sensitivity * axis_pair.y() + rotation.y).clamp(-PI/2., PI/2.);
-    
-            let quat = Quat::from_rotation_y(rotation.x) * Quat::from_rotation_x(rotation.y);
+    // Sticks and keys turn at a fixed speed, mouse motion is already a distance
+    let mut delta = action_state.clamped_axis_pair(Action::RotateCamera)
+        .map_or(Vec2::ZERO, |axis_pair| axis_pair.xy()) * settings.rotate_speed * time.delta_seconds();
+    let motion: Vec2 = mouse_motion.iter().map(|motion| motion.delta).sum();
+    if action_state.pressed(Action::Orbit) {
+        // Dragging right turns the view right, dragging up looks up
+        delta += Vec2::new(motion.x, -motion.y) * settings.orbit_sensitivity;
+    }
+    if bindings.invert_x { delta.x = -delta.x; }
+    if bindings.invert_y { delta.y = -delta.y; }
+
+    orbit.turn(delta);
 
-            trans.rotation = quat;
+    for wheel in mouse_wheel.iter() {
+        let lines = match wheel.unit {
+            MouseScrollUnit::Line => wheel.y,
+            MouseScrollUnit::Pixel => wheel.y / 100.0,
+        };
+        orbit.distance *= (1.0 - settings.zoom_step).powf(lines);
+    }
+    orbit.distance = orbit.distance.clamp(settings.min_distance, settings.max_distance);
 
-            trans.translation = focus_trans.translation + trans.back() * camera_dist;
+    let Some((focus, focus_trans, interpolation, velocity)) = focus_query.iter().next() else { return };
+    if orbit.focus != Some(focus) {
+        // Blend over to the new focus instead of snapping to it
+        if orbit.focus.is_some() {
+            orbit.handoff_left = settings.handoff_secs;
         }
+        orbit.focus = Some(focus);
     }
+    orbit.handoff_left = (orbit.handoff_left - time.delta_seconds()).max(0.0);
+    let stiffness = if orbit.handoff_left > 0.0 { settings.handoff_stiffness } else { settings.follow_stiffness };
+    // Per frame lag weight for the same damping at any frame rate
+    smoother.set_lag_weight((-stiffness * time.delta_seconds()).exp());
+
+    // Follow the rendered position of interpolated bodies
+    let focus_trans = interpolation
+        .and_then(|interpolation| interpolation.interpolated(interpolation_alpha(&fixed_time)))
+        .unwrap_or(*focus_trans);
+    let look_ahead = velocity.map_or(Vec3::ZERO, |velocity| {
+        (velocity.linvel * settings.look_ahead_secs).clamp_length_max(settings.max_look_ahead)
+    });
+
+    let rotation = Quat::from_rotation_y(orbit.yaw) * Quat::from_rotation_x(orbit.pitch);
+    look.target = focus_trans.translation + look_ahead;
+    look.eye = look.target + rotation * Vec3::Z * orbit.distance;
 }
 
+// Spring arm on the damped camera: snap in front of obstacles right away, ease back out when the view clears
+fn avoid_obstructions(
+    mut camera_query: Query<(&mut Transform, &mut OrbitCamera, &LookTransform), With<MainCamera>>,
+    settings: Res<CameraSettings>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let Ok((mut trans, mut orbit, look)) = camera_query.get_single_mut() else { return };
+
+    let offset = trans.translation - look.target;
+    let (direction, length) = (offset.normalize_or_zero(), offset.length());
+    if direction == Vec3::ZERO {
+        return;
+    }
+    let clear_length = spring_arm_length(&rapier_context, look.target, direction, length);
+    orbit.arm_length = if clear_length < orbit.arm_length {
+        clear_length
+    } else {
+        let ease = 1.0 - (-settings.arm_return_rate * time.delta_seconds()).exp();
+        orbit.arm_length + (clear_length - orbit.arm_length) * ease
+    };
+    trans.translation = look.target + direction * orbit.arm_length.min(length);
+}
+
+fn overview_camera(
+    mut camera_query: Query<(&mut Transform, &CameraMode), With<MainCamera>>,
+    level_query: Query<&Children, With<Level>>,
+    q_global: Query<&GlobalTransform>,
+) {
+    let Ok((mut trans, mode)) = camera_query.get_single_mut() else { return };
+    if *mode != CameraMode::Overview {
+        return;
+    }
+
+    let points: Vec<Vec3> = level_query.iter()
+        .flat_map(|children| children.iter())
+        .filter_map(|child| q_global.get(*child).ok())
+        .map(|global| global.translation())
+        .collect();
+    if points.is_empty() {
+        return;
+    }
+    let min = points.iter().fold(Vec3::splat(f32::MAX), |min, point| min.min(*point));
+    let max = points.iter().fold(Vec3::splat(f32::MIN), |max, point| max.max(*point));
+    let center = (min + max) / 2.0;
+    // High enough to fit the level in a 45 degree view
+    let extent = (max.x - min.x).max(max.z - min.z);
+    let height = max.y - center.y + extent + 20.0;
+
+    *trans = Transform::from_translation(center + Vec3::Y * height).looking_at(center, Vec3::NEG_Z);
+}
+
+// Runs after the mode systems have placed the camera, and blends that in from the old view
+pub(crate) fn blend_camera_transition(
+    mut commands: Commands,
+    mut camera_query: Query<(Entity, &mut Transform, &mut CameraTransition), With<MainCamera>>,
+    time: Res<Time>,
+) {
+    for (camera, mut trans, mut transition) in camera_query.iter_mut() {
+        transition.timer.tick(time.delta());
+        let t = transition.timer.percent();
+        let t = t * t * (3.0 - 2.0 * t);
+        trans.translation = transition.from.translation.lerp(trans.translation, t);
+        trans.rotation = transition.from.rotation.slerp(trans.rotation, t);
+        if transition.timer.finished() {
+            commands.entity(camera).remove::<CameraTransition>();
+        }
+    }
+}
 
 fn reset_focus(
     mut commands: Commands,
@@ -108,40 +423,20 @@ fn reset_focus(
 /// Can rotate and zoom out from focus object
 
 
-// #[derive(Resource)]
-// pub struct FreeCam(bool);
-
-// pub fn free_cam(res: Res<FreeCam>) -> bool { res.0 }
-
-// fn toggle_freecam_stuff (
-//     mut commands: Commands,
-//     query: Query<Entity, With<MainCamera>>,
-//     mut free_cam: ResMut<FreeCam>,
-//     keyboard_input: Res<Input<KeyCode>>,
-// ) {
-//     if keyboard_input.just_pressed(KeyCode::T) {
-//         if free_cam.0 {
-//             let cam = query.single();
-//             commands.entity(cam).remove::<FlyCam>();
-//             commands.entity(cam).insert(LookTransform{ eye: Vec3::ZERO, target: Vec3::ZERO, up: Vec3::Y});
-//             free_cam.0 = false;
-//         } else {
-//             let cam = query.single();
-//             commands.entity(cam).insert(FlyCam);
-//             commands.entity(cam).remove::<LookTransform>();
-//             free_cam.0 = true;
-//         }
-//     }
-// }
-
 #[derive(Component)]
 pub struct MainCamera;
 
 fn setup(
     mut commands: Commands,
+    bindings: Res<Bindings>,
 ) {
     let eye = Vec3::new(0., 2., 10.);
     let target = Vec3::new(0., 0., 0.);
+    let bound_actions = BoundActions(vec![
+        Action::RotateCamera, Action::Orbit, Action::CycleCamera,
+        Action::PhotoMode, Action::TakePhoto, Action::PhotoLens, Action::PhotoBrighter, Action::PhotoDarker, Action::PhotoBloom,
+        Action::ToggleEditor,
+    ]);
 
     commands.spawn((
         Camera3dBundle {
@@ -153,21 +448,31 @@ fn setup(
             ..default()
         },
         BloomSettings::default(),
-        InputManagerBundle {
-                action_state: ActionState::default(),
-                input_map: InputMap::default()
-                    .insert(DualAxis::right_stick(), Action::RotateCamera)
-                    .insert(VirtualDPad::wasd(), Action::RotateCamera)
-                    // .insert(DualAxis::mouse_motion(), Action::RotateCamera)
-                    // .insert(VirtualDPad::arrow_keys(), Action::Aim)
-                    .insert(KeyCode::Space, Action::Shoot)
-                    .build(),
-            },
+        bound_actions.input_manager_bundle(&bindings),
+        bound_actions,
+        OrbitCamera::default(),
+        follow_bundle(),
+        CameraMode::default(),
         Name::new("Camera"),
         MainCamera,
     ));
 }
 
+fn cleanup_camera(
+    mut commands: Commands,
+    query: Query<Entity, With<MainCamera>>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // Free flying may have grabbed the cursor
+    if let Ok(mut window) = q_window.get_single_mut() {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
+    }
+}
+
 // fn reset_camera(
 //     launcher_q: Query<&Transform, With<Launcher>>,
 //     mut look_q: Query<&mut LookTransform, With<MainCamera>>,
@@ -194,4 +499,36 @@ fn setup(
 //             }
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaw_wraps_around() {
+        let mut orbit = OrbitCamera { yaw: PI - 0.1, ..default() };
+        orbit.turn(Vec2::new(-0.3, 0.0));
+        assert!((orbit.yaw - (-PI + 0.2)).abs() < 1e-5);
+        orbit.turn(Vec2::new(0.3, 0.0));
+        assert!((orbit.yaw - (PI - 0.1)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn many_turns_stay_in_range() {
+        let mut orbit = OrbitCamera::default();
+        for _ in 0..1000 {
+            orbit.turn(Vec2::new(0.7, 0.0));
+            assert!((-PI..PI).contains(&orbit.yaw));
+        }
+    }
+
+    #[test]
+    fn pitch_stops_at_straight_up_and_down() {
+        let mut orbit = OrbitCamera::default();
+        orbit.turn(Vec2::new(0.0, 10.0));
+        assert_eq!(orbit.pitch, PI/2.);
+        orbit.turn(Vec2::new(0.0, -20.0));
+        assert_eq!(orbit.pitch, -PI/2.);
+    }
+}
@@ -0,0 +1,109 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{prelude::*, core_pipeline::bloom::BloomSettings, input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::EguiContext;
+
+use crate::{AppState, actions::{Action, Bindings, BoundActions}, game::gameplay_elements::launcher::Launcher};
+use super::{Selection, keyboard_over_ui, pointer_over_ui};
+
+pub(crate) struct EditorCameraPlugin;
+
+impl Plugin for EditorCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(spawn_editor_camera.in_schedule(OnEnter(AppState::Editor)))
+            .add_system(cleanup_editor_camera.in_schedule(OnExit(AppState::Editor)))
+            .add_system(move_editor_camera.in_set(OnUpdate(AppState::Editor)))
+            ;
+    }
+}
+
+/// Orbits `focus` with the right mouse button, pans with the middle one and zooms with the wheel
+#[derive(Component)]
+pub(crate) struct EditorCamera {
+    pub(crate) focus: Vec3,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl EditorCamera {
+    fn transform(&self) -> Transform {
+        let rotation = Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(self.pitch);
+        Transform::from_translation(self.focus + rotation * Vec3::Z * self.distance).with_rotation(rotation)
+    }
+}
+
+const ORBIT_SENSITIVITY: f32 = 0.005;
+const PAN_SENSITIVITY: f32 = 0.0015;
+const ZOOM_STEP: f32 = 0.1;
+
+fn spawn_editor_camera(
+    mut commands: Commands,
+    bindings: Res<Bindings>,
+    q_launcher: Query<&Transform, With<Launcher>>,
+) {
+    let focus = q_launcher.iter().next().map_or(Vec3::ZERO, |launcher| launcher.translation);
+    let editor_camera = EditorCamera { focus, yaw: 0.0, pitch: -0.6, distance: 40.0 };
+    let bound_actions = BoundActions(vec![Action::ToggleEditor]);
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera { hdr: true, ..default() },
+            transform: editor_camera.transform(),
+            ..default()
+        },
+        BloomSettings::default(),
+        bound_actions.input_manager_bundle(&bindings),
+        bound_actions,
+        editor_camera,
+        Name::new("Editor camera"),
+    ));
+}
+
+fn cleanup_editor_camera(
+    mut commands: Commands,
+    query: Query<Entity, With<EditorCamera>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn move_editor_camera(
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    selection: Res<Selection>,
+    q_global: Query<&GlobalTransform>,
+    mut q_camera: Query<(&mut Transform, &mut EditorCamera)>,
+    mut q_egui: Query<&mut EguiContext, With<PrimaryWindow>>,
+) {
+    let Ok((mut trans, mut camera)) = q_camera.get_single_mut() else { return };
+    let motion: Vec2 = mouse_motion.iter().map(|motion| motion.delta).sum();
+    let scroll: f32 = mouse_wheel.iter()
+        .map(|wheel| match wheel.unit {
+            MouseScrollUnit::Line => wheel.y,
+            MouseScrollUnit::Pixel => wheel.y / 100.0,
+        })
+        .sum();
+
+    if mouse.pressed(MouseButton::Right) {
+        camera.yaw -= motion.x * ORBIT_SENSITIVITY;
+        camera.pitch = (camera.pitch - motion.y * ORBIT_SENSITIVITY).clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+    }
+    if mouse.pressed(MouseButton::Middle) {
+        // The level follows the cursor
+        let pan = (trans.left() * motion.x + trans.up() * motion.y) * camera.distance * PAN_SENSITIVITY;
+        camera.focus += pan;
+    }
+    if !pointer_over_ui(&mut q_egui) {
+        camera.distance = (camera.distance * (1.0 - ZOOM_STEP).powf(scroll)).clamp(1.0, 1000.0);
+    }
+    if keys.just_pressed(KeyCode::F) && !keyboard_over_ui(&mut q_egui) {
+        if let Some(selected) = selection.active.and_then(|entity| q_global.get(entity).ok()) {
+            camera.focus = selected.translation();
+        }
+    }
+    *trans = camera.transform();
+}
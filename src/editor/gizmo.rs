@@ -0,0 +1,269 @@
+use bevy::{prelude::*, render::primitives::Aabb, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::EguiContext;
+
+use crate::{AppState, game::gameplay_elements::{launcher::{Launcher, LauncherAim}, wall}};
+use super::{Selection, history::{EditCommand, History}, palette::Placing, snap::SnapSettings, camera::EditorCamera, cursor_ray, keyboard_over_ui, pointer_over_ui, ray_box_distance};
+
+pub(crate) struct GizmoPlugin;
+
+impl Plugin for GizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GizmoMode>()
+            .init_resource::<GizmoDrag>()
+            .add_system(spawn_gizmo.in_schedule(OnEnter(AppState::Editor)))
+            .add_system(cleanup_gizmo.in_schedule(OnExit(AppState::Editor)))
+            .add_system(switch_gizmo_mode.in_set(OnUpdate(AppState::Editor)))
+            .add_system(start_drag.in_set(OnUpdate(AppState::Editor)))
+            .add_system(drag_gizmo
+                .in_set(OnUpdate(AppState::Editor))
+                .after(start_drag)
+            )
+            .add_system(place_gizmo
+                .in_set(OnUpdate(AppState::Editor))
+                .after(drag_gizmo)
+            )
+            ;
+    }
+}
+
+/// What dragging a gizmo handle does to the selection
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub(crate) const ALL: [GizmoMode; 3] = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "Move (W)",
+            GizmoMode::Rotate => "Rotate (E)",
+            GizmoMode::Scale => "Scale (R)",
+        }
+    }
+}
+
+/// Handle drag in progress, edits are made relative to where it started
+pub(crate) struct Drag {
+    pub(crate) entity: Entity,
+    pub(crate) start: Transform,
+    axis: Vec3,
+    axis_index: usize,
+    start_along: f32,
+    start_cursor: Vec2,
+    gizmo_size: f32,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct GizmoDrag(pub(crate) Option<Drag>);
+
+#[derive(Component)]
+struct GizmoRoot;
+
+#[derive(Component)]
+struct GizmoHandle {
+    axis: usize,
+}
+
+const AXES: [Vec3; 3] = [Vec3::X, Vec3::Y, Vec3::Z];
+/// Gizmo length as a fraction of its distance to the camera, so it keeps its size on screen
+const GIZMO_SCREEN_SIZE: f32 = 0.15;
+const ROTATE_SENSITIVITY: f32 = 0.01;
+const MIN_SCALE: f32 = 0.01;
+
+fn spawn_gizmo(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        SpatialBundle { visibility: Visibility::Hidden, ..default() },
+        GizmoRoot,
+        Name::new("Gizmo"),
+    ))
+    .with_children(|parent| {
+        for (axis, color) in [Color::RED, Color::GREEN, Color::BLUE].into_iter().enumerate() {
+            let size = AXES[axis] * 0.9 + Vec3::splat(0.1);
+            parent.spawn((
+                PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z))),
+                    material: materials.add(StandardMaterial { base_color: color, unlit: true, ..default() }),
+                    transform: Transform::from_translation(AXES[axis] * 0.5),
+                    ..default()
+                },
+                GizmoHandle { axis },
+            ));
+        }
+    });
+}
+
+fn cleanup_gizmo(
+    mut commands: Commands,
+    query: Query<Entity, With<GizmoRoot>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn switch_gizmo_mode(
+    keys: Res<Input<KeyCode>>,
+    mut mode: ResMut<GizmoMode>,
+    mut q_egui: Query<&mut EguiContext, With<PrimaryWindow>>,
+) {
+    if keyboard_over_ui(&mut q_egui) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::W) {
+        *mode = GizmoMode::Translate;
+    } else if keys.just_pressed(KeyCode::E) {
+        *mode = GizmoMode::Rotate;
+    } else if keys.just_pressed(KeyCode::R) {
+        *mode = GizmoMode::Scale;
+    }
+}
+
+// Scaling works along the element's own axes, moving and rotating along the world's
+fn axis_direction(mode: GizmoMode, axis: usize, transform: &Transform) -> Vec3 {
+    match mode {
+        GizmoMode::Scale => transform.rotation * AXES[axis],
+        _ => AXES[axis],
+    }
+}
+
+/// Parameter of the point on the axis line through `origin` closest to `ray`
+fn along_axis(origin: Vec3, axis: Vec3, ray: Ray) -> Option<f32> {
+    let w = origin - ray.origin;
+    let b = axis.dot(ray.direction);
+    let denominator = 1.0 - b * b;
+    // Looking straight down the axis
+    if denominator < 1e-4 {
+        return None;
+    }
+    Some((b * ray.direction.dot(w) - axis.dot(w)) / denominator)
+}
+
+fn place_gizmo(
+    selection: Res<Selection>,
+    mode: Res<GizmoMode>,
+    q_global: Query<&GlobalTransform, Without<GizmoRoot>>,
+    q_camera: Query<&GlobalTransform, With<EditorCamera>>,
+    mut q_root: Query<(&mut Transform, &mut Visibility), With<GizmoRoot>>,
+) {
+    let Ok((mut trans, mut visibility)) = q_root.get_single_mut() else { return };
+    let (Some(selected), Ok(camera)) = (selection.active.and_then(|entity| q_global.get(entity).ok()), q_camera.get_single()) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let (_, rotation, translation) = selected.to_scale_rotation_translation();
+    *visibility = Visibility::Inherited;
+    trans.translation = translation;
+    trans.rotation = if *mode == GizmoMode::Scale { rotation } else { Quat::IDENTITY };
+    trans.scale = Vec3::splat(camera.translation().distance(translation) * GIZMO_SCREEN_SIZE);
+}
+
+pub(crate) fn start_drag(
+    mouse: Res<Input<MouseButton>>,
+    mut drag: ResMut<GizmoDrag>,
+    selection: Res<Selection>,
+    mode: Res<GizmoMode>,
+    placing: Res<Placing>,
+    mut q_egui: Query<&mut EguiContext, With<PrimaryWindow>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    q_root: Query<&Transform, With<GizmoRoot>>,
+    q_handles: Query<(&GizmoHandle, &Aabb, &GlobalTransform)>,
+    q_transform: Query<&Transform, Without<GizmoRoot>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || placing.0.is_some() || pointer_over_ui(&mut q_egui) {
+        return;
+    }
+    let Some(entity) = selection.active else { return };
+    let (Ok(window), Ok((camera, camera_transform))) = (q_window.get_single(), q_camera.get_single()) else { return };
+    let (Some(cursor), Some(ray)) = (window.cursor_position(), cursor_ray(window, camera, camera_transform)) else { return };
+
+    let hit = q_handles.iter()
+        .filter_map(|(handle, aabb, transform)| Some((handle.axis, ray_box_distance(ray, aabb, transform)?)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    let (Some((axis_index, _)), Ok(start), Ok(root)) = (hit, q_transform.get(entity), q_root.get_single()) else { return };
+
+    let axis = axis_direction(*mode, axis_index, start);
+    drag.0 = Some(Drag {
+        entity,
+        start: *start,
+        axis,
+        axis_index,
+        start_along: along_axis(start.translation, axis, ray).unwrap_or_default(),
+        start_cursor: cursor,
+        gizmo_size: root.scale.x,
+    });
+}
+
+fn drag_gizmo(
+    mouse: Res<Input<MouseButton>>,
+    mut drag: ResMut<GizmoDrag>,
+    mode: Res<GizmoMode>,
+    snap: Res<SnapSettings>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    mut q_transform: Query<&mut Transform, Without<GizmoRoot>>,
+    mut q_aim: Query<&mut LauncherAim>,
+    q_launcher: Query<(), With<Launcher>>,
+    q_box: Query<&wall::Box>,
+    mut history: ResMut<History>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        // The whole drag is one edit
+        let Some(finished) = drag.0.take() else { return };
+        let Ok(&to) = q_transform.get(finished.entity) else { return };
+        if to != finished.start {
+            history.record(if q_launcher.contains(finished.entity) {
+                EditCommand::MoveLauncher { from: finished.start, to }
+            } else {
+                EditCommand::Transform { entity: finished.entity, from: finished.start, to }
+            });
+        }
+        return;
+    }
+    let Some(active) = &drag.0 else { return };
+    let (Ok(window), Ok((camera, camera_transform))) = (q_window.get_single(), q_camera.get_single()) else { return };
+    let (Some(cursor), Some(ray)) = (window.cursor_position(), cursor_ray(window, camera, camera_transform)) else { return };
+
+    let mut transform = active.start;
+    match *mode {
+        GizmoMode::Translate => {
+            if let Some(along) = along_axis(active.start.translation, active.axis, ray) {
+                transform.translation += active.axis * (along - active.start_along);
+                transform.translation[active.axis_index] = snap.position(transform.translation[active.axis_index]);
+            }
+        }
+        GizmoMode::Rotate => {
+            let angle = (cursor.x - active.start_cursor.x) * ROTATE_SENSITIVITY;
+            transform.rotation = snap.rotation(Quat::from_axis_angle(active.axis, angle) * active.start.rotation);
+        }
+        GizmoMode::Scale => {
+            if let Some(along) = along_axis(active.start.translation, active.axis, ray) {
+                let factor = 1.0 + (along - active.start_along) / active.gizmo_size;
+                let scale = (active.start.scale[active.axis_index] * factor).max(MIN_SCALE);
+                // Walls snap their scaled size rather than the scale
+                let size = q_box.get(active.entity).map_or(1.0, |box_dims| [box_dims.x, box_dims.y, box_dims.z][active.axis_index]);
+                transform.scale[active.axis_index] = snap.size(scale * size) / size;
+            }
+        }
+    }
+
+    if let Ok(mut current) = q_transform.get_mut(active.entity) {
+        if *current != transform {
+            *current = transform;
+        }
+    }
+    // The launcher takes its rotation from its aim
+    if let Ok(mut aim) = q_aim.get_mut(active.entity) {
+        *aim = LauncherAim::from_rotation(transform.rotation);
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, ecs::{entity::EntityMap, system::Command}, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::EguiContext;
+
+use crate::AppState;
+use crate::game::level::{Level, level_manager::level_type_registry};
+use crate::game::gameplay_elements::{launcher::{Launcher, LauncherAim}, wall::{self, SetWallType, WallType}};
+use super::{Selection, gizmo::GizmoDrag};
+
+pub(crate) struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<History>()
+            .add_system(undo_redo.in_set(OnUpdate(AppState::Editor)))
+            .add_system(forget_level_edits)
+            ;
+    }
+}
+
+/// Oldest edits are forgotten past this
+const MAX_HISTORY: usize = 100;
+
+/// A reversible level edit, recorded after it has been made
+pub(crate) enum EditCommand {
+    /// An element was added to the level, holds the element while it's undone
+    Spawn { entity: Entity, removed: Option<DynamicScene> },
+    /// Elements were removed from the level together, holds them until it's undone
+    Delete { elements: Vec<(Entity, Option<DynamicScene>)> },
+    Transform { entity: Entity, from: Transform, to: Transform },
+    Resize { entity: Entity, from: wall::Box, to: wall::Box },
+    /// A wall swapped type, each side keeps its settings for when it's swapped back in
+    Retype { entity: Entity, from: WallType, to: WallType },
+    /// Several elements moved at once by align or distribute
+    Arrange { moves: Vec<(Entity, Transform, Transform)> },
+    /// Levels have a single launcher so this applies to whichever entity it is
+    MoveLauncher { from: Transform, to: Transform },
+}
+
+impl EditCommand {
+    /// Makes the edit again, returns the new ids of elements brought back
+    fn apply(&mut self, world: &mut World) -> Vec<(Entity, Entity)> {
+        match self {
+            EditCommand::Spawn { entity, removed } => restore_element(world, *entity, removed).into_iter().collect(),
+            EditCommand::Delete { elements } => {
+                for (entity, removed) in elements.iter_mut() {
+                    *removed = remove_element(world, *entity);
+                }
+                Vec::new()
+            }
+            EditCommand::Transform { entity, to, .. } => {
+                set_transform(world, *entity, *to);
+                Vec::new()
+            }
+            EditCommand::Resize { entity, to, .. } => {
+                set_box(world, *entity, *to);
+                Vec::new()
+            }
+            EditCommand::Retype { entity, from, to } => {
+                *from = set_wall_type(world, *entity, *to).unwrap_or(*from);
+                Vec::new()
+            }
+            EditCommand::Arrange { moves } => {
+                for (entity, _, to) in moves.iter() {
+                    set_transform(world, *entity, *to);
+                }
+                Vec::new()
+            }
+            EditCommand::MoveLauncher { to, .. } => {
+                move_launcher(world, *to);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Takes the edit back, returns the new ids of elements brought back
+    fn revert(&mut self, world: &mut World) -> Vec<(Entity, Entity)> {
+        match self {
+            EditCommand::Spawn { entity, removed } => {
+                *removed = remove_element(world, *entity);
+                Vec::new()
+            }
+            EditCommand::Delete { elements } => elements.iter_mut()
+                .filter_map(|(entity, removed)| restore_element(world, *entity, removed))
+                .collect(),
+            EditCommand::Transform { entity, from, .. } => {
+                set_transform(world, *entity, *from);
+                Vec::new()
+            }
+            EditCommand::Resize { entity, from, .. } => {
+                set_box(world, *entity, *from);
+                Vec::new()
+            }
+            EditCommand::Retype { entity, from, to } => {
+                *to = set_wall_type(world, *entity, *from).unwrap_or(*to);
+                Vec::new()
+            }
+            EditCommand::Arrange { moves } => {
+                for (entity, from, _) in moves.iter() {
+                    set_transform(world, *entity, *from);
+                }
+                Vec::new()
+            }
+            EditCommand::MoveLauncher { from, .. } => {
+                move_launcher(world, *from);
+                Vec::new()
+            }
+        }
+    }
+
+    fn remap(&mut self, from: Entity, to: Entity) {
+        match self {
+            EditCommand::Spawn { entity, .. }
+            | EditCommand::Transform { entity, .. }
+            | EditCommand::Resize { entity, .. }
+            | EditCommand::Retype { entity, .. } if *entity == from => *entity = to,
+            EditCommand::Delete { elements } => {
+                for (entity, _) in elements.iter_mut().filter(|(entity, _)| *entity == from) {
+                    *entity = to;
+                }
+            }
+            EditCommand::Arrange { moves } => {
+                for (entity, _, _) in moves.iter_mut().filter(|(entity, _, _)| *entity == from) {
+                    *entity = to;
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Undo and redo stacks of level edits, kept while play-testing
+///
+/// Entries for elements that no longer exist, like after finishing the level, do nothing.
+#[derive(Resource, Default)]
+pub(crate) struct History {
+    undo: VecDeque<EditCommand>,
+    redo: Vec<EditCommand>,
+}
+
+impl History {
+    pub(crate) fn record(&mut self, command: EditCommand) {
+        self.redo.clear();
+        self.undo.push_back(command);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.pop_front();
+        }
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // Elements brought back from a snapshot get a new entity
+    fn remap(&mut self, from: Entity, to: Entity) {
+        for command in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            command.remap(from, to);
+        }
+    }
+}
+
+pub(crate) fn undo(world: &mut World) {
+    let Some(mut command) = world.resource_mut::<History>().undo.pop_back() else { return };
+    let remapped = command.revert(world);
+    let mut history = world.resource_mut::<History>();
+    for (from, to) in remapped {
+        history.remap(from, to);
+        command.remap(from, to);
+    }
+    history.redo.push(command);
+}
+
+pub(crate) fn redo(world: &mut World) {
+    let Some(mut command) = world.resource_mut::<History>().redo.pop() else { return };
+    let remapped = command.apply(world);
+    let mut history = world.resource_mut::<History>();
+    for (from, to) in remapped {
+        history.remap(from, to);
+        command.remap(from, to);
+    }
+    history.undo.push_back(command);
+}
+
+/// Removes elements from the level so they can be undone in one go
+pub(crate) struct DeleteElements(pub(crate) Vec<Entity>);
+
+impl Command for DeleteElements {
+    fn write(self, world: &mut World) {
+        let elements: Vec<_> = self.0.into_iter()
+            .filter_map(|entity| Some((entity, Some(remove_element(world, entity)?))))
+            .collect();
+        if !elements.is_empty() {
+            world.resource_mut::<History>().record(EditCommand::Delete { elements });
+        }
+    }
+}
+
+/// Snapshot of the element with what a level file holds, the rest is rebuilt when it's added back
+fn remove_element(world: &mut World, entity: Entity) -> Option<DynamicScene> {
+    world.get_entity(entity)?;
+    let mut builder = DynamicSceneBuilder::from_world_with_type_registry(world, level_type_registry());
+    builder.extract_entity(entity);
+    let scene = builder.build();
+    world.entity_mut(entity).despawn_recursive();
+    Some(scene)
+}
+
+fn restore_element(world: &mut World, entity: Entity, removed: &mut Option<DynamicScene>) -> Option<(Entity, Entity)> {
+    let level = world.query_filtered::<Entity, With<Level>>().iter(world).next()?;
+    let scene = removed.as_ref()?;
+    let mut entity_map = EntityMap::default();
+    if let Err(error) = scene.write_to_world(world, &mut entity_map) {
+        warn!("Couldn't restore level element: {}", error);
+        return None;
+    }
+    let restored = entity_map.values().next()?;
+    world.entity_mut(level).add_child(restored);
+    *removed = None;
+    Some((entity, restored))
+}
+
+fn set_transform(world: &mut World, entity: Entity, transform: Transform) {
+    if let Some(mut current) = world.get_mut::<Transform>(entity) {
+        *current = transform;
+    }
+    // The launcher takes its rotation from its aim
+    if let Some(mut aim) = world.get_mut::<LauncherAim>(entity) {
+        *aim = LauncherAim::from_rotation(transform.rotation);
+    }
+}
+
+fn set_box(world: &mut World, entity: Entity, box_dims: wall::Box) {
+    if let Some(mut current) = world.get_mut::<wall::Box>(entity) {
+        *current = box_dims;
+    }
+}
+
+/// Returns the type swapped out, settings changed since the swap included
+fn set_wall_type(world: &mut World, entity: Entity, wall: WallType) -> Option<WallType> {
+    let current = WallType::of(&world.get_entity(entity)?)?;
+    SetWallType { entity, wall }.write(world);
+    Some(current)
+}
+
+fn move_launcher(world: &mut World, transform: Transform) {
+    if let Some(launcher) = world.query_filtered::<Entity, With<Launcher>>().iter(world).next() {
+        set_transform(world, launcher, transform);
+    }
+}
+
+// Edits of another level can't be undone in this one
+fn forget_level_edits(
+    q_level: Query<(), Added<Level>>,
+    mut history: ResMut<History>,
+    mut selection: ResMut<Selection>,
+) {
+    if !q_level.is_empty() {
+        *history = History::default();
+        selection.select(None);
+    }
+}
+
+// Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes
+fn undo_redo(world: &mut World) {
+    let keys = world.resource::<Input<KeyCode>>();
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    let (undo_pressed, redo_pressed) = (
+        ctrl && !shift && keys.just_pressed(KeyCode::Z),
+        ctrl && (keys.just_pressed(KeyCode::Y) || shift && keys.just_pressed(KeyCode::Z)),
+    );
+    if !undo_pressed && !redo_pressed || world.resource::<GizmoDrag>().0.is_some() {
+        return;
+    }
+    // Text fields have their own undo
+    let typing = world
+        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+        .get_single_mut(world)
+        .map_or(false, |mut egui| egui.get_mut().wants_keyboard_input());
+    if typing {
+        return;
+    }
+
+    if undo_pressed {
+        undo(world);
+    } else {
+        redo(world);
+    }
+    deselect_removed(world);
+}
+
+/// Drops elements undo or redo removed from the selection
+pub(crate) fn deselect_removed(world: &mut World) {
+    let removed: Vec<Entity> = world.resource::<Selection>().group.iter()
+        .copied()
+        .filter(|entity| world.get_entity(*entity).is_none())
+        .collect();
+    let mut selection = world.resource_mut::<Selection>();
+    for entity in removed {
+        selection.toggle(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor_world() -> (World, Entity) {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.insert_resource(level_type_registry());
+        let level = world.spawn(Level).id();
+        (world, level)
+    }
+
+    fn spawn_element(world: &mut World, level: Entity, transform: Transform) -> Entity {
+        let element = world.spawn(transform).id();
+        world.entity_mut(level).add_child(element);
+        element
+    }
+
+    fn moved(entity: Entity, x: f32) -> EditCommand {
+        EditCommand::Transform { entity, from: Transform::from_xyz(x - 1.0, 0.0, 0.0), to: Transform::from_xyz(x, 0.0, 0.0) }
+    }
+
+    fn x(world: &World, entity: Entity) -> f32 {
+        world.get::<Transform>(entity).unwrap().translation.x
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_stacks() {
+        let (mut world, level) = editor_world();
+        let element = spawn_element(&mut world, level, Transform::from_xyz(2.0, 0.0, 0.0));
+        world.resource_mut::<History>().record(moved(element, 1.0));
+        world.resource_mut::<History>().record(moved(element, 2.0));
+
+        undo(&mut world);
+        assert_eq!(x(&world, element), 1.0);
+        undo(&mut world);
+        assert_eq!(x(&world, element), 0.0);
+        assert!(!world.resource::<History>().can_undo());
+
+        redo(&mut world);
+        assert_eq!(x(&world, element), 1.0);
+        redo(&mut world);
+        assert_eq!(x(&world, element), 2.0);
+        assert!(!world.resource::<History>().can_redo());
+    }
+
+    #[test]
+    fn recording_clears_redo() {
+        let (mut world, level) = editor_world();
+        let element = spawn_element(&mut world, level, Transform::default());
+        world.resource_mut::<History>().record(moved(element, 1.0));
+        undo(&mut world);
+        assert!(world.resource::<History>().can_redo());
+
+        world.resource_mut::<History>().record(moved(element, 3.0));
+        assert!(!world.resource::<History>().can_redo());
+    }
+
+    #[test]
+    fn oldest_edits_are_forgotten() {
+        let mut history = History::default();
+        for i in 0..MAX_HISTORY + 10 {
+            history.record(moved(Entity::from_raw(0), i as f32));
+        }
+        assert_eq!(history.undo.len(), MAX_HISTORY);
+        let Some(EditCommand::Transform { to, .. }) = history.undo.front() else { panic!() };
+        assert_eq!(to.translation.x, 10.0);
+    }
+
+    #[test]
+    fn undone_delete_brings_the_element_back_for_older_edits() {
+        let (mut world, level) = editor_world();
+        let element = spawn_element(&mut world, level, Transform::from_xyz(1.0, 0.0, 0.0));
+        world.resource_mut::<History>().record(moved(element, 1.0));
+        DeleteElements(vec![element]).write(&mut world);
+        assert!(world.get_entity(element).is_none());
+
+        undo(&mut world);
+        let restored = world.get::<Children>(level).unwrap()[0];
+        assert_eq!(x(&world, restored), 1.0);
+
+        // The move recorded before the delete follows the element to its new id
+        undo(&mut world);
+        assert_eq!(x(&world, restored), 0.0);
+        redo(&mut world);
+        redo(&mut world);
+        assert!(world.get_entity(restored).is_none());
+    }
+
+    #[test]
+    fn group_delete_is_one_edit() {
+        let (mut world, level) = editor_world();
+        let elements: Vec<Entity> = (0..3)
+            .map(|i| spawn_element(&mut world, level, Transform::from_xyz(i as f32, 0.0, 0.0)))
+            .collect();
+        DeleteElements(elements.clone()).write(&mut world);
+        assert!(elements.iter().all(|element| world.get_entity(*element).is_none()));
+
+        undo(&mut world);
+        let mut restored: Vec<f32> = world.get::<Children>(level).unwrap().iter().map(|child| x(&world, *child)).collect();
+        restored.sort_by(f32::total_cmp);
+        assert_eq!(restored, vec![0.0, 1.0, 2.0]);
+        assert!(!world.resource::<History>().can_undo());
+
+        redo(&mut world);
+        assert!(world.get::<Children>(level).map_or(true, |children| children.is_empty()));
+    }
+}
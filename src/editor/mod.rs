@@ -0,0 +1,212 @@
+mod camera;
+mod gizmo;
+mod history;
+mod palette;
+mod snap;
+
+use bevy::{prelude::*, render::primitives::Aabb, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::{EguiContext, EguiPlugin};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{AppState, actions::Action, camera::MainCamera, photo::in_photo_mode};
+use crate::game::{GameState, CurrentLevel, gameplay_elements::ball::{BallState, GolfBall}};
+use crate::game::level::{Level, intro_playing, level_manager::{LevelFile, LoadLevelEvent}};
+use self::{camera::{EditorCamera, EditorCameraPlugin}, gizmo::{GizmoDrag, GizmoPlugin}, history::{DeleteElements, HistoryPlugin}, palette::{PalettePlugin, Placing}, snap::SnapSettings};
+
+/// Level editing as an app state of its own, reached from the menu or while playing
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugin(EguiPlugin);
+        }
+        app
+            .init_resource::<Selection>()
+            .init_resource::<SnapSettings>()
+            .add_plugin(EditorCameraPlugin)
+            .add_plugin(GizmoPlugin)
+            .add_plugin(HistoryPlugin)
+            .add_plugin(PalettePlugin)
+            .add_system(enter_editor.in_schedule(OnEnter(AppState::Editor)))
+            .add_system(exit_editor.in_schedule(OnExit(AppState::Editor)))
+            .add_system(toggle_editor
+                .run_if(not(in_photo_mode))
+                .run_if(not(intro_playing))
+            )
+            .add_system(select_on_click
+                .in_set(OnUpdate(AppState::Editor))
+                .after(gizmo::start_drag)
+                .before(palette::place_on_surface)
+            )
+            .add_system(delete_selected.in_set(OnUpdate(AppState::Editor)))
+            ;
+    }
+}
+
+/// Level elements being edited, always direct children of the `Level`
+#[derive(Resource, Default)]
+pub(crate) struct Selection {
+    /// The element the gizmo and palette edit, the last one clicked
+    pub(crate) active: Option<Entity>,
+    /// Everything shift clicked for aligning and distributing, `active` included
+    pub(crate) group: Vec<Entity>,
+}
+
+impl Selection {
+    pub(crate) fn select(&mut self, entity: Option<Entity>) {
+        self.active = entity;
+        self.group = entity.into_iter().collect();
+    }
+
+    /// Adds `entity` to the group, or takes it out when it's already there
+    pub(crate) fn toggle(&mut self, entity: Entity) {
+        if let Some(index) = self.group.iter().position(|selected| *selected == entity) {
+            self.group.remove(index);
+            if self.active == Some(entity) {
+                self.active = self.group.last().copied();
+            }
+        } else {
+            self.group.push(entity);
+            self.active = Some(entity);
+        }
+    }
+}
+
+// Coming from the menu there is no level yet, coming from play the ball in flight is removed
+fn enter_editor(
+    mut commands: Commands,
+    q_level: Query<(), With<Level>>,
+    q_ball: Query<Entity, With<GolfBall>>,
+    current_level: Res<CurrentLevel>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut ball_state: ResMut<NextState<BallState>>,
+    mut events: EventWriter<LoadLevelEvent>,
+) {
+    if game_state.0 != GameState::InProgress {
+        next_game_state.set(GameState::InProgress);
+    }
+    ball_state.set(BallState::Aiming);
+    for ball in q_ball.iter() {
+        commands.entity(ball).despawn_recursive();
+    }
+    if q_level.is_empty() {
+        events.send(LoadLevelEvent { level: LevelFile::Index(current_level.index) });
+    }
+}
+
+fn exit_editor(
+    mut selection: ResMut<Selection>,
+    mut drag: ResMut<GizmoDrag>,
+    mut placing: ResMut<Placing>,
+) {
+    selection.select(None);
+    drag.0 = None;
+    placing.0 = None;
+}
+
+fn toggle_editor(
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    q_actions: Query<&ActionState<Action>, Or<(With<MainCamera>, With<EditorCamera>)>>,
+) {
+    if !q_actions.iter().any(|action_state| action_state.just_pressed(Action::ToggleEditor)) {
+        return;
+    }
+    match state.0 {
+        AppState::Playing => next_state.set(AppState::Editor),
+        AppState::Editor => next_state.set(AppState::Playing),
+        _ => (),
+    }
+}
+
+/// Whether egui is using the pointer, clicks on the palette shouldn't reach the level
+pub(crate) fn pointer_over_ui(q_egui: &mut Query<&mut EguiContext, With<PrimaryWindow>>) -> bool {
+    q_egui.get_single_mut().map_or(false, |mut egui| egui.get_mut().is_pointer_over_area())
+}
+
+pub(crate) fn keyboard_over_ui(q_egui: &mut Query<&mut EguiContext, With<PrimaryWindow>>) -> bool {
+    q_egui.get_single_mut().map_or(false, |mut egui| egui.get_mut().wants_keyboard_input())
+}
+
+/// World space ray under the cursor
+pub(crate) fn cursor_ray(window: &Window, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Ray> {
+    camera.viewport_to_world(camera_transform, window.cursor_position()?)
+}
+
+/// Distance along `ray` to the bounding box, tested in the box's local space
+pub(crate) fn ray_box_distance(ray: Ray, aabb: &Aabb, transform: &GlobalTransform) -> Option<f32> {
+    let to_local = transform.affine().inverse();
+    let origin = to_local.transform_point3(ray.origin);
+    let direction = to_local.transform_vector3(ray.direction);
+    let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+
+    // Slab test, the local direction isn't normalized but the ray parameter is shared with world space
+    let t1 = (min - origin) / direction;
+    let t2 = (max - origin) / direction;
+    let near = t1.min(t2).max_element();
+    let far = t1.max(t2).min_element();
+    (near <= far && far >= 0.0).then_some(near.max(0.0))
+}
+
+/// The `Level` child `entity` belongs to, walking up from meshes nested in scenes like terrain
+pub(crate) fn level_element(mut entity: Entity, q_parent: &Query<&Parent>, q_level: &Query<(), With<Level>>) -> Option<Entity> {
+    while let Ok(parent) = q_parent.get(entity) {
+        if q_level.contains(parent.get()) {
+            return Some(entity);
+        }
+        entity = parent.get();
+    }
+    None
+}
+
+// Shift click adds to the selection
+fn select_on_click(
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    drag: Res<GizmoDrag>,
+    placing: Res<Placing>,
+    mut selection: ResMut<Selection>,
+    mut q_egui: Query<&mut EguiContext, With<PrimaryWindow>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<EditorCamera>>,
+    q_bounds: Query<(Entity, &Aabb, &GlobalTransform, &ComputedVisibility)>,
+    q_parent: Query<&Parent>,
+    q_level: Query<(), With<Level>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || drag.0.is_some() || placing.0.is_some() || pointer_over_ui(&mut q_egui) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (q_window.get_single(), q_camera.get_single()) else { return };
+    let Some(ray) = cursor_ray(window, camera, camera_transform) else { return };
+
+    let hit = q_bounds.iter()
+        .filter(|(_, _, _, visibility)| visibility.is_visible_in_hierarchy())
+        .filter_map(|(entity, aabb, transform, _)| Some((level_element(entity, &q_parent, &q_level)?, ray_box_distance(ray, aabb, transform)?)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    match hit.map(|(entity, _)| entity) {
+        Some(entity) if shift => selection.toggle(entity),
+        // Shift clicking empty space keeps the selection
+        None if shift => (),
+        hit => selection.select(hit),
+    }
+    if let Some(entity) = selection.active {
+        info!("Selected {:?}", entity);
+    }
+}
+
+fn delete_selected(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut selection: ResMut<Selection>,
+    mut q_egui: Query<&mut EguiContext, With<PrimaryWindow>>,
+) {
+    if !keys.just_pressed(KeyCode::Delete) || keyboard_over_ui(&mut q_egui) {
+        return;
+    }
+    // One undo brings the whole group back
+    commands.add(DeleteElements(std::mem::take(&mut selection.group)));
+    selection.select(None);
+}
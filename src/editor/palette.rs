@@ -0,0 +1,322 @@
+use bevy::{prelude::*, ecs::system::Command, window::PrimaryWindow};
+use bevy_inspector_egui::{egui::{self, Visuals}, bevy_egui::EguiContext};
+
+use crate::AppState;
+use crate::game::level::{Level, LevelSettings, level_manager::{LevelFile, SaveLevelEvent}};
+use crate::game::gameplay_elements::{goal::Goal, launcher::Launcher, terrain::GltfTerrain, wall::{self, BounceWall, LowGravWall, PlainWall, SetWallType, WallKind, WallType}};
+use super::{Selection, camera::EditorCamera, gizmo::GizmoMode, history::{self, DeleteElements, EditCommand, History}, snap::{self, SnapSettings}};
+
+pub(crate) struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Placing>()
+            .add_system(palette_ui.in_set(OnUpdate(AppState::Editor)))
+            .add_system(place_on_surface.in_set(OnUpdate(AppState::Editor)))
+            ;
+    }
+}
+
+/// Everything the palette can place in a level
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Element {
+    PlainWall,
+    BounceWall,
+    LowGravWall,
+    Goal,
+    Launcher,
+    Terrain,
+}
+
+impl Element {
+    pub(crate) const ALL: [Element; 6] = [
+        Element::PlainWall,
+        Element::BounceWall,
+        Element::LowGravWall,
+        Element::Goal,
+        Element::Launcher,
+        Element::Terrain,
+    ];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Element::PlainWall => "Plain wall",
+            Element::BounceWall => "Bounce wall",
+            Element::LowGravWall => "Gravity wall",
+            Element::Goal => "Goal",
+            Element::Launcher => "Launcher",
+            Element::Terrain => "Terrain",
+        }
+    }
+
+    /// Resting on a surface, standing along its normal except for the launcher which stays level
+    fn resting_on(&self, point: Vec3, normal: Vec3) -> Transform {
+        let up = if *self == Element::Launcher { Vec3::Y } else { normal };
+        // Goals are 2 high and launchers 1
+        let half_height = match self {
+            Element::PlainWall | Element::BounceWall | Element::LowGravWall => wall::Box::default().y / 2.0,
+            Element::Goal => 1.0,
+            Element::Launcher => 0.5,
+            Element::Terrain => 0.0,
+        };
+        Transform::from_translation(point + up * half_height).with_rotation(Quat::from_rotation_arc(Vec3::Y, up))
+    }
+}
+
+/// Element waiting for a click on the level to be placed, in surface placement mode
+#[derive(Resource, Default)]
+pub(crate) struct Placing(pub(crate) Option<Placement>);
+
+pub(crate) struct Placement {
+    element: Element,
+    terrain_path: String,
+}
+
+struct PaletteState {
+    name_input: String,
+    terrain_path: String,
+    /// Box dimensions of the wall being resized before the edit started
+    resize_from: Option<(Entity, wall::Box)>,
+}
+
+impl Default for PaletteState {
+    fn default() -> Self {
+        Self {
+            name_input: "level_1".to_string(),
+            terrain_path: "models/terrain.glb#Scene0".to_string(),
+            resize_from: None,
+        }
+    }
+}
+
+/// Adds `element` to the level, the level only ever has one launcher so that one is moved instead
+fn place_element(world: &mut World, element: Element, transform: Transform, terrain_path: &str) -> Option<Entity> {
+    let level = world.query_filtered::<Entity, With<Level>>().iter(world).next()?;
+    if element == Element::Launcher {
+        if let Some((launcher, &from)) = world.query_filtered::<(Entity, &Transform), With<Launcher>>().iter(world).next() {
+            // Keeping its aim
+            let to = Transform { rotation: from.rotation, ..transform };
+            world.entity_mut(launcher).insert(to);
+            world.resource_mut::<History>().record(EditCommand::MoveLauncher { from, to });
+            return Some(launcher);
+        }
+    }
+
+    let mut entity = world.spawn(SpatialBundle::from_transform(transform));
+    match element {
+        Element::PlainWall => entity.insert((PlainWall, wall::Box::default())),
+        Element::BounceWall => entity.insert((BounceWall::default(), wall::Box::default())),
+        Element::LowGravWall => entity.insert((LowGravWall::default(), wall::Box::default())),
+        Element::Goal => entity.insert(Goal),
+        Element::Launcher => entity.insert(Launcher),
+        Element::Terrain => entity.insert(GltfTerrain { path: terrain_path.to_string(), ..default() }),
+    };
+    let id = entity.id();
+    world.entity_mut(level).add_child(id);
+    world.resource_mut::<History>().record(EditCommand::Spawn { entity: id, removed: None });
+    info!("Placed {:?}", element);
+    Some(id)
+}
+
+fn palette_ui(
+    world: &mut World,
+    mut state: Local<PaletteState>,
+) {
+    let mut egui_context = world
+        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+        .single(world)
+        .clone();
+
+    egui::Window::new("Level editor").show(egui_context.get_mut(), |ui| {
+        ui.ctx().set_visuals(Visuals::light());
+        ui.label("Level");
+        ui.text_edit_singleline(&mut state.name_input);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                world.send_event::<SaveLevelEvent>(SaveLevelEvent { level: LevelFile::parse(&state.name_input) });
+            }
+            if ui.button("Play").clicked() {
+                world.resource_mut::<NextState<AppState>>().set(AppState::Playing);
+            }
+        });
+        ui.horizontal(|ui| {
+            let (can_undo, can_redo) = {
+                let history = world.resource::<History>();
+                (history.can_undo(), history.can_redo())
+            };
+            if ui.add_enabled(can_undo, egui::Button::new("Undo")).clicked() {
+                history::undo(world);
+                history::deselect_removed(world);
+            }
+            if ui.add_enabled(can_redo, egui::Button::new("Redo")).clicked() {
+                history::redo(world);
+                history::deselect_removed(world);
+            }
+        });
+
+        ui.label("Tool");
+        ui.horizontal(|ui| {
+            let mut mode = world.resource_mut::<GizmoMode>();
+            for option in GizmoMode::ALL {
+                ui.selectable_value(&mut *mode, option, option.label());
+            }
+        });
+
+        ui.label("Snapping");
+        ui.horizontal(|ui| {
+            let mut snap = world.resource_mut::<SnapSettings>();
+            ui.checkbox(&mut snap.enabled, "Grid");
+            ui.add(egui::DragValue::new(&mut snap.position).speed(0.1).clamp_range(0.1..=100.0));
+            ui.label("Angle");
+            ui.add(egui::DragValue::new(&mut snap.rotation).clamp_range(1.0..=90.0).suffix("°"));
+            ui.label("Size");
+            ui.add(egui::DragValue::new(&mut snap.size).speed(0.1).clamp_range(0.1..=100.0));
+        });
+        ui.checkbox(&mut world.resource_mut::<SnapSettings>().surface, "Place on the surface under the cursor");
+
+        ui.label("Place");
+        ui.horizontal_wrapped(|ui| {
+            for element in Element::ALL {
+                if !ui.button(element.label()).clicked() {
+                    continue;
+                }
+                if world.resource::<SnapSettings>().surface {
+                    world.resource_mut::<Placing>().0 = Some(Placement { element, terrain_path: state.terrain_path.clone() });
+                    continue;
+                }
+                let focus = world.query::<&EditorCamera>().iter(world).next().map_or(Vec3::ZERO, |camera| camera.focus);
+                let snap = world.resource::<SnapSettings>();
+                let position = Vec3::new(snap.position(focus.x), snap.position(focus.y), snap.position(focus.z));
+                if let Some(placed) = place_element(world, element, Transform::from_translation(position), &state.terrain_path) {
+                    world.resource_mut::<Selection>().select(Some(placed));
+                }
+            }
+        });
+        if let Some(placement) = &world.resource::<Placing>().0 {
+            ui.label(format!("Click on the level to place the {}, Escape cancels", placement.element.label().to_lowercase()));
+        }
+        ui.horizontal(|ui| {
+            ui.label("Terrain scene:");
+            ui.text_edit_singleline(&mut state.terrain_path);
+        });
+        if world.query::<&LevelSettings>().iter(world).next().is_none() && ui.button("Level settings").clicked() {
+            if let Some(level) = world.query_filtered::<Entity, With<Level>>().iter(world).next() {
+                let id = world.spawn((LevelSettings::default(), Name::new("Level settings"))).id();
+                world.entity_mut(level).add_child(id);
+            }
+        }
+
+        ui.label("Selected");
+        let selection = world.resource::<Selection>().active;
+        // A resize is finished when another element gets selected
+        if let Some((resized, from)) = state.resize_from.filter(|(resized, _)| Some(*resized) != selection) {
+            record_resize(world, resized, from);
+            state.resize_from = None;
+        }
+        match selection {
+            Some(entity) => {
+                let name = world.get::<Name>(entity).map_or_else(|| format!("{:?}", entity), |name| name.to_string());
+                ui.horizontal(|ui| {
+                    ui.label(name);
+                    if ui.button("Delete").clicked() {
+                        DeleteElements(vec![entity]).write(world);
+                        history::deselect_removed(world);
+                    }
+                });
+                if let Some(from) = world.get_entity(entity).and_then(|selected| WallType::of(&selected)) {
+                    ui.horizontal(|ui| {
+                        ui.label("Type:");
+                        for kind in WallKind::ALL {
+                            if ui.selectable_label(from.kind() == kind, kind.label()).clicked() && from.kind() != kind {
+                                let to = WallType::from(kind);
+                                SetWallType { entity, wall: to }.write(world);
+                                world.resource_mut::<History>().record(EditCommand::Retype { entity, from, to });
+                            }
+                        }
+                    });
+                }
+                if let Some(&before) = world.get::<wall::Box>(entity) {
+                    let mut box_dims = before;
+                    let mut finished = false;
+                    ui.horizontal(|ui| {
+                        ui.label("Box:");
+                        for value in [&mut box_dims.x, &mut box_dims.y, &mut box_dims.z] {
+                            let response = ui.add(egui::DragValue::new(value).speed(0.1).clamp_range(0.1..=500.0));
+                            finished |= response.drag_released() || response.lost_focus();
+                        }
+                    });
+                    if box_dims != before {
+                        state.resize_from.get_or_insert((entity, before));
+                        world.entity_mut(entity).insert(box_dims);
+                    }
+                    if let Some((resized, from)) = state.resize_from.filter(|_| finished) {
+                        let snap = world.resource::<SnapSettings>();
+                        let snapped = wall::Box { x: snap.size(box_dims.x), y: snap.size(box_dims.y), z: snap.size(box_dims.z) };
+                        if snapped != box_dims {
+                            world.entity_mut(entity).insert(snapped);
+                        }
+                        record_resize(world, resized, from);
+                        state.resize_from = None;
+                    }
+                }
+            }
+            None => {
+                ui.label("Nothing, click an element");
+            }
+        }
+
+        let grouped = world.resource::<Selection>().group.len();
+        if grouped >= 2 {
+            ui.label(format!("{} selected, shift click to add or remove", grouped));
+            ui.horizontal(|ui| {
+                ui.label("Align to selected:");
+                for (axis, label) in ["X", "Y", "Z"].into_iter().enumerate() {
+                    if ui.button(label).clicked() {
+                        snap::align(world, axis);
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Distribute:");
+                for (axis, label) in ["X", "Y", "Z"].into_iter().enumerate() {
+                    if ui.add_enabled(grouped >= 3, egui::Button::new(label)).clicked() {
+                        snap::distribute(world, axis);
+                    }
+                }
+            });
+        }
+        ui.label("Right drag orbits, middle drag pans, F focuses the selection");
+    });
+}
+
+fn record_resize(world: &mut World, entity: Entity, from: wall::Box) {
+    let Some(&to) = world.get::<wall::Box>(entity) else { return };
+    if to != from {
+        world.resource_mut::<History>().record(EditCommand::Resize { entity, from, to });
+    }
+}
+
+// Left click rests the element on the level under the cursor
+pub(crate) fn place_on_surface(world: &mut World) {
+    if world.resource::<Placing>().0.is_none() {
+        return;
+    }
+    if world.resource::<Input<KeyCode>>().just_pressed(KeyCode::Escape) {
+        world.resource_mut::<Placing>().0 = None;
+        return;
+    }
+    let over_ui = world
+        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+        .get_single_mut(world)
+        .map_or(false, |mut egui| egui.get_mut().is_pointer_over_area());
+    if over_ui || !world.resource::<Input<MouseButton>>().just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some((point, normal)) = snap::surface_under_cursor(world) else { return };
+    let Some(placement) = world.resource_mut::<Placing>().0.take() else { return };
+    let transform = placement.element.resting_on(point, normal);
+    if let Some(placed) = place_element(world, placement.element, transform, &placement.terrain_path) {
+        world.resource_mut::<Selection>().select(Some(placed));
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_rapier3d::prelude::*;
+
+use super::{Selection, camera::EditorCamera, history::{EditCommand, History}};
+
+/// Grid steps for editing, new levels come out with tidy numbers
+#[derive(Resource)]
+pub(crate) struct SnapSettings {
+    pub(crate) enabled: bool,
+    /// Position grid in world units
+    pub(crate) position: f32,
+    /// Rotation increment in degrees
+    pub(crate) rotation: f32,
+    /// Wall size grid in world units
+    pub(crate) size: f32,
+    /// New elements are placed by clicking on the level instead of in front of the camera
+    pub(crate) surface: bool,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self { enabled: true, position: 1.0, rotation: 15.0, size: 0.5, surface: false }
+    }
+}
+
+impl SnapSettings {
+    pub(crate) fn position(&self, value: f32) -> f32 {
+        snap(value, self.position, self.enabled)
+    }
+
+    /// Sizes don't snap down to nothing
+    pub(crate) fn size(&self, value: f32) -> f32 {
+        if !self.enabled || self.size <= 0.0 {
+            return value;
+        }
+        snap(value, self.size, true).max(self.size)
+    }
+
+    /// Snaps yaw, pitch and roll on their own so a snapped rotation reads back as round angles
+    pub(crate) fn rotation(&self, rotation: Quat) -> Quat {
+        if !self.enabled || self.rotation <= 0.0 {
+            return rotation;
+        }
+        let step = self.rotation.to_radians();
+        let (yaw, pitch, roll) = rotation.to_euler(EulerRot::YXZ);
+        Quat::from_euler(EulerRot::YXZ, snap(yaw, step, true), snap(pitch, step, true), snap(roll, step, true))
+    }
+}
+
+fn snap(value: f32, step: f32, enabled: bool) -> f32 {
+    if !enabled || step <= 0.0 {
+        return value;
+    }
+    (value / step).round() * step
+}
+
+/// Point and normal of the level geometry under the cursor, the ground plane if there's nothing there
+pub(crate) fn surface_under_cursor(world: &mut World) -> Option<(Vec3, Vec3)> {
+    let cursor = world.query_filtered::<&Window, With<PrimaryWindow>>().get_single(world).ok()?.cursor_position()?;
+    let mut q_camera = world.query_filtered::<(&Camera, &GlobalTransform), With<EditorCamera>>();
+    let (camera, camera_transform) = q_camera.get_single(world).ok()?;
+    let ray = camera.viewport_to_world(camera_transform, cursor)?;
+
+    let hit = world.resource::<RapierContext>()
+        .cast_ray_and_get_normal(ray.origin, ray.direction, f32::MAX, true, QueryFilter::new().exclude_sensors());
+    if let Some((_, intersection)) = hit {
+        return Some((intersection.point, intersection.normal));
+    }
+    let distance = ray.intersect_plane(Vec3::ZERO, Vec3::Y)?;
+    Some((ray.get_point(distance), Vec3::Y))
+}
+
+/// Lines the selected elements up with the active one along `axis`
+pub(crate) fn align(world: &mut World, axis: usize) {
+    let Some(active) = world.resource::<Selection>().active else { return };
+    let Some(target) = world.get::<Transform>(active).map(|transform| transform.translation[axis]) else { return };
+    arrange(world, axis, |_, _| target);
+}
+
+/// Spaces the selected elements evenly along `axis` between the outermost two
+pub(crate) fn distribute(world: &mut World, axis: usize) {
+    arrange(world, axis, |index, (first, last, count)| {
+        first + (last - first) * index as f32 / (count - 1) as f32
+    });
+}
+
+// Moves the selection in order along `axis`, to where `place` says given the order and the outermost positions
+fn arrange(world: &mut World, axis: usize, place: impl Fn(usize, (f32, f32, usize)) -> f32) {
+    let group = world.resource::<Selection>().group.clone();
+    let mut elements: Vec<(Entity, Transform)> = group.into_iter()
+        .filter_map(|entity| Some((entity, *world.get::<Transform>(entity)?)))
+        .collect();
+    if elements.len() < 2 {
+        return;
+    }
+    elements.sort_by(|(_, a), (_, b)| a.translation[axis].total_cmp(&b.translation[axis]));
+    let ends = (elements[0].1.translation[axis], elements[elements.len() - 1].1.translation[axis], elements.len());
+
+    let mut moves = Vec::new();
+    for (index, (entity, from)) in elements.into_iter().enumerate() {
+        let mut to = from;
+        to.translation[axis] = place(index, ends);
+        if to != from {
+            if let Some(mut transform) = world.get_mut::<Transform>(entity) {
+                *transform = to;
+            }
+            moves.push((entity, from, to));
+        }
+    }
+    if !moves.is_empty() {
+        world.resource_mut::<History>().record(EditCommand::Arrange { moves });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::history::undo;
+
+    fn selected(positions: &[Vec3]) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        let group: Vec<Entity> = positions.iter().map(|position| world.spawn(Transform::from_translation(*position)).id()).collect();
+        world.insert_resource(Selection { active: group.first().copied(), group: group.clone() });
+        (world, group)
+    }
+
+    fn translation(world: &World, entity: Entity) -> Vec3 {
+        world.get::<Transform>(entity).unwrap().translation
+    }
+
+    #[test]
+    fn positions_snap_to_the_grid_only_when_enabled() {
+        let mut settings = SnapSettings { position: 0.5, ..default() };
+        assert_eq!(settings.position(1.3), 1.5);
+        assert_eq!(settings.position(-0.2), 0.0);
+        settings.enabled = false;
+        assert_eq!(settings.position(1.3), 1.3);
+    }
+
+    #[test]
+    fn sizes_never_snap_to_nothing() {
+        let settings = SnapSettings { size: 0.5, ..default() };
+        assert_eq!(settings.size(1.1), 1.0);
+        assert_eq!(settings.size(0.1), 0.5);
+        assert_eq!(SnapSettings { size: 0.0, ..default() }.size(0.1), 0.1);
+    }
+
+    #[test]
+    fn rotations_snap_each_angle() {
+        let settings = SnapSettings { rotation: 15.0, ..default() };
+        let rotation = Quat::from_euler(EulerRot::YXZ, 47f32.to_radians(), 8f32.to_radians(), 0.0);
+        let expected = Quat::from_euler(EulerRot::YXZ, 45f32.to_radians(), 15f32.to_radians(), 0.0);
+        assert!(settings.rotation(rotation).abs_diff_eq(expected, 1e-5));
+    }
+
+    #[test]
+    fn align_moves_the_group_to_the_active_element_in_one_edit() {
+        let (mut world, group) = selected(&[Vec3::new(1.0, 0.0, 0.0), Vec3::new(3.0, 2.0, 0.0), Vec3::new(-2.0, 4.0, 1.0)]);
+        align(&mut world, 0);
+        assert!(group.iter().all(|entity| translation(&world, *entity).x == 1.0));
+        assert_eq!(translation(&world, group[2]).y, 4.0);
+
+        undo(&mut world);
+        assert_eq!(translation(&world, group[1]).x, 3.0);
+        assert_eq!(translation(&world, group[2]).x, -2.0);
+    }
+
+    #[test]
+    fn distribute_spaces_the_group_between_the_outermost() {
+        let (mut world, group) = selected(&[Vec3::new(0.0, 0.0, 9.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 2.0)]);
+        distribute(&mut world, 2);
+        let mut z: Vec<f32> = group.iter().map(|entity| translation(&world, *entity).z).collect();
+        z.sort_by(f32::total_cmp);
+        assert_eq!(z, vec![0.0, 3.0, 6.0, 9.0]);
+    }
+
+    #[test]
+    fn arranging_a_single_element_records_nothing() {
+        let (mut world, _) = selected(&[Vec3::ONE]);
+        distribute(&mut world, 0);
+        assert!(!world.resource::<History>().can_undo());
+    }
+}
@@ -6,7 +6,7 @@ pub struct LightingPlugin;
 impl Plugin for LightingPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_system(setup.in_schedule(OnEnter(AppState::Playing)));
+            .add_system(setup.in_schedule(OnExit(AppState::Loading)));
     }
 }
 
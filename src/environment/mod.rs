@@ -1,5 +1,5 @@
 mod test_env;
-mod physics;
+pub mod physics;
 mod physics_test_env;
 mod lighting;
 
@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, ecs::schedule::ScheduleLabel, transform::TransformSystem};
 use bevy_rapier3d::prelude::*;
 
 pub struct PhysicsPlugin;
@@ -6,17 +6,145 @@ pub struct PhysicsPlugin;
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .insert_resource(FixedTime::new_from_secs(FIXED_TIMESTEP))
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default().with_default_system_setup(false))
+            .insert_resource(rapier_configuration())
             .add_plugin(RapierDebugRenderPlugin::default())
+            .add_schedule(PhysicsSchedule, physics_schedule())
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.configure_sets((
+                    SimulationSet::PrePhysics,
+                    SimulationSet::Physics,
+                    SimulationSet::PostPhysics,
+                ).chain());
+            })
+            .add_system(run_physics_schedule
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::Physics)
+            )
+            .add_system(record_physics_transforms
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
+            )
+            .add_system(interpolate_render_transforms
+                .in_base_set(CoreSet::PostUpdate)
+                .after(TransformSystem::TransformPropagate)
+            )
             // .add_system(print_sensor_collison.in_set(OnUpdate(AppState::Playing)))
             ;
     }
 }
 
+/// Length of one simulation tick, physics and gameplay advance in steps of exactly this long
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+
+/// Physics substeps per tick, keeps fast balls from skipping through thin walls
+pub const PHYSICS_SUBSTEPS: usize = 4;
+
+pub fn rapier_configuration() -> RapierConfiguration {
+    RapierConfiguration {
+        timestep_mode: TimestepMode::Fixed {
+            dt: FIXED_TIMESTEP,
+            substeps: PHYSICS_SUBSTEPS,
+        },
+        ..default()
+    }
+}
+
+/// Order of the systems in each fixed tick (`CoreSchedule::FixedUpdate`)
+///
+/// Gameplay reading input runs before the physics step, gameplay reacting to
+/// collisions runs after it. Systems in these sets must use `FixedTime::period`
+/// instead of `Time::delta`.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum SimulationSet {
+    PrePhysics,
+    Physics,
+    PostPhysics,
+}
+
+#[derive(ScheduleLabel, Debug, Hash, PartialEq, Eq, Clone)]
+struct PhysicsSchedule;
+
+fn physics_schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+    schedule
+        .configure_sets((
+            PhysicsSet::SyncBackend,
+            PhysicsSet::SyncBackendFlush,
+            PhysicsSet::StepSimulation,
+            PhysicsSet::Writeback,
+        ).chain())
+        .add_systems(RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackend)
+            .in_base_set(PhysicsSet::SyncBackend))
+        .add_systems(RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackendFlush)
+            .in_base_set(PhysicsSet::SyncBackendFlush))
+        .add_systems(RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::StepSimulation)
+            .in_base_set(PhysicsSet::StepSimulation))
+        .add_systems(RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::Writeback)
+            .in_base_set(PhysicsSet::Writeback));
+    schedule
+}
+
+fn run_physics_schedule(world: &mut World) {
+    world.run_schedule(PhysicsSchedule);
+}
+
+/// Renders the entity between its last two physics states instead of jumping tick to tick
+#[derive(Component, Default)]
+pub struct RenderInterpolation {
+    previous: Option<Transform>,
+    current: Option<Transform>,
+}
+
+impl RenderInterpolation {
+    /// Transform to show, `alpha` is how far real time is into the next tick
+    pub fn interpolated(&self, alpha: f32) -> Option<Transform> {
+        let (previous, current) = (self.previous?, self.current?);
+        Some(Transform {
+            translation: previous.translation.lerp(current.translation, alpha),
+            rotation: previous.rotation.slerp(current.rotation, alpha),
+            scale: current.scale,
+        })
+    }
+}
+
+/// How far real time is into the next fixed tick, from 0 to 1
+pub fn interpolation_alpha(fixed_time: &FixedTime) -> f32 {
+    (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).clamp(0.0, 1.0)
+}
+
+fn record_physics_transforms(
+    mut query: Query<(&Transform, &mut RenderInterpolation)>,
+) {
+    for (transform, mut interpolation) in query.iter_mut() {
+        interpolation.previous = Some(interpolation.current.unwrap_or(*transform));
+        interpolation.current = Some(*transform);
+    }
+}
+
+// Only the rendered `GlobalTransform` is touched, without change detection, so the
+// physics backend never sees the interpolated position
+fn interpolate_render_transforms(
+    fixed_time: Res<FixedTime>,
+    mut query: Query<(&RenderInterpolation, &mut GlobalTransform, Option<&Parent>)>,
+    q_parent: Query<&GlobalTransform, Without<RenderInterpolation>>,
+) {
+    let alpha = interpolation_alpha(&fixed_time);
+    for (interpolation, mut global, parent) in query.iter_mut() {
+        let Some(transform) = interpolation.interpolated(alpha) else { continue };
+        let parent_global = parent
+            .and_then(|parent| q_parent.get(parent.get()).ok())
+            .copied()
+            .unwrap_or_default();
+        *global.bypass_change_detection() = parent_global.mul_transform(transform);
+    }
+}
+
 // fn print_sensor_collison(
 //     mut collisions: EventReader<CollisionEvent>,
 // ) {
 //     for collision in collisions.iter() {
 //         info!("Collision: {:?}", collision);
 //     }
-// }
\ No newline at end of file
+// }
@@ -1,9 +1,10 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use crate::AppState;
+use crate::environment::physics::SimulationSet;
 use crate::loading::FontAssets;
 
-use super::{level::level_manager::LoadLevelEvent, gameplay_elements::{goal::Goal, ball::BallState}};
+use super::{level::{Level, level_manager::{LevelFile, LoadLevelEvent}}, gameplay_elements::{goal::Goal, ball::BallState}};
 
 #[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum GameState {
@@ -33,9 +34,11 @@ impl Plugin for GameManagerPlugin {
                 .in_set(OnUpdate(AppState::Playing))
             )
             .add_system(level_complete
-                .in_set(OnUpdate(AppState::Playing))
-                .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(BallState::InPlay))
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
+                .run_if(in_state(AppState::Playing))
+                .run_if(in_state(GameState::InProgress))
+                .run_if(in_state(BallState::InPlay))
             )
             .add_system(hud_game_complete.run_if(on_event::<GameCompleteEvent>()))
             .add_system(hud_level_complete.run_if(on_event::<LevelCompletEvent>()))
@@ -50,8 +53,8 @@ impl Plugin for GameManagerPlugin {
 const LEVEL_COUNT: usize = 3;
 
 #[derive(Resource)]
-struct CurrentLevel {
-    index: usize,
+pub(crate) struct CurrentLevel {
+    pub(crate) index: usize,
     _shots: usize,
 }
 
@@ -74,11 +77,17 @@ impl Default for NewLevelTimer {
 
 fn auto_start_first_game(
     mut state: ResMut<NextState<GameState>>,
+    mut ball_state: ResMut<NextState<BallState>>,
     mut events: EventWriter<LoadLevelEvent>,
+    q_level: Query<(), With<Level>>,
 ) {
     state.set(GameState::InProgress);
+    ball_state.set(BallState::Aiming);
 
-    events.send(LoadLevelEvent { level: 1 });
+    // Coming back from the editor plays the level being edited
+    if q_level.is_empty() {
+        events.send(LoadLevelEvent { level: LevelFile::Index(1) });
+    }
 }
 
 fn auto_load_next_level (
@@ -92,7 +101,7 @@ fn auto_load_next_level (
     if local.timer.tick(time.delta()).finished() {
         state.set(GameState::InProgress);
         ball_state.set(BallState::Aiming);
-        events.send(LoadLevelEvent { level: res.index });
+        events.send(LoadLevelEvent { level: LevelFile::Index(res.index) });
         local.timer.reset();
     }
 }
@@ -140,7 +149,7 @@ fn level_complete(
 }
 
 fn hud_game_complete(
-    mut text_query: Query<&mut Text>,
+    mut text_query: Query<&mut Text, With<HudGameStatus>>,
 ) {
     for mut text in text_query.iter_mut() {
         text.sections[0].value = "ALL LEVELS COMPLETE".to_string();
@@ -148,7 +157,7 @@ fn hud_game_complete(
 }
 
 fn hud_level_complete(
-    mut text_query: Query<&mut Text>,
+    mut text_query: Query<&mut Text, With<HudGameStatus>>,
 ) {
     for mut text in text_query.iter_mut() {
         text.sections[0].value = "Level Complete".to_string();
@@ -156,7 +165,7 @@ fn hud_level_complete(
 }
 
 fn hud_status_reset(
-    mut text_query: Query<&mut Text>,
+    mut text_query: Query<&mut Text, With<HudGameStatus>>,
 ) {
     for mut text in text_query.iter_mut() {
         text.sections[0].value = "".to_string();
@@ -2,6 +2,8 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
 use crate::{game::GameState, AppState};
+use crate::environment::physics::RenderInterpolation;
+use super::{status_effect::StatusEffects, spin::BallContact};
 
 #[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum BallState {
@@ -35,14 +37,39 @@ impl Plugin for GolfBallPlugin {
 #[reflect(Component)]
 pub(crate) struct GolfBall;
 
+/// Collider radius of every golf ball
+pub const BALL_RADIUS: f32 = 1.0;
+pub(crate) const BALL_LINEAR_DAMPING: f32 = 0.4;
+pub(crate) const BALL_ANGULAR_DAMPING: f32 = 0.4;
+
+/// Physics shared by every golf ball, CCD keeps fast shots from tunneling through walls
+#[derive(Bundle)]
+pub struct BallPhysicsBundle {
+    pub collider: Collider,
+    pub rigidbody: RigidBody,
+    pub damping: Damping,
+    pub ccd: Ccd,
+}
+
+impl Default for BallPhysicsBundle {
+    fn default() -> Self {
+        Self {
+            collider: Collider::ball(BALL_RADIUS),
+            rigidbody: RigidBody::Dynamic,
+            damping: Damping { linear_damping: BALL_LINEAR_DAMPING, angular_damping: BALL_ANGULAR_DAMPING },
+            ccd: Ccd::enabled(),
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub(crate) struct GolfBallBundle {
     #[bundle]
     pub(crate) pbr: PbrBundle,
     pub(crate) name: Name,
-    pub(crate) collider: Collider,
+    #[bundle]
+    pub(crate) physics: BallPhysicsBundle,
     pub(crate) restitution: Restitution,
-    pub(crate) rigidbody: RigidBody,
     pub(crate) golf_ball: GolfBall,
 }
 
@@ -51,9 +78,8 @@ impl Default for GolfBallBundle {
         Self {
             pbr: PbrBundle::default(),
             name: Name::new("Golf ball"),
-            collider: Collider::ball(1.),
+            physics: BallPhysicsBundle::default(),
             restitution: Restitution::new(1.),
-            rigidbody: RigidBody::Dynamic,
             golf_ball: GolfBall, 
         }
     }
@@ -81,10 +107,11 @@ fn golfball_added(
                 transform: *transform,
                 ..default()
             },
-            Collider::ball(1.),
-            Damping { linear_damping: 0.4, angular_damping: 0.4 },
+            BallPhysicsBundle::default(),
+            RenderInterpolation::default(),
+            StatusEffects::default(),
+            BallContact::default(),
             // Restitution::new(1.),
-            RigidBody::Dynamic,
             // LockedAxes::all(),
             // GravityScale(0.0),
             // LaunchTimer(Timer::from_seconds(1.0, TimerMode::Repeating)),
@@ -1,4 +1,5 @@
 use super::{create_physical_box, ball::{GolfBall, BallState}};
+use crate::environment::physics::SimulationSet;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
@@ -6,7 +7,10 @@ pub struct DeathZonePlugin;
 
 impl Plugin for DeathZonePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(death_zone_collision);
+        app.add_system(death_zone_collision
+            .in_schedule(CoreSchedule::FixedUpdate)
+            .in_set(SimulationSet::PostPhysics)
+        );
     }
 }
 
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use crate::{game::GameState, AppState};
+use crate::{game::GameState, in_level};
 
 use super::create_physical_box;
 
@@ -12,7 +12,7 @@ impl Plugin for GoalPlugin {
             .register_type::<Goal>() // TODO remove?
             .add_system(goal_added
                 .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(AppState::Playing))
+                .run_if(in_level)
             )
             ;
     }
@@ -1,14 +1,19 @@
+use std::f32::consts::{PI, TAU};
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::{LockedAxes, Velocity};
-use leafwing_input_manager::prelude::*;
+use leafwing_input_manager::{prelude::*, plugin::InputManagerSystem};
 
-use crate::camera::Focus;
-use crate::game::level::Level;
-use crate::{actions::Action, game::game_manager::GameState, AppState, loading::AudioAssets};
+use crate::environment::physics::SimulationSet;
+
+use crate::camera::{Focus, free_flying};
+use crate::game::level::{Level, LevelSettings, intro_playing};
+use crate::{actions::{Action, Bindings, BoundActions}, game::game_manager::GameState, AppState, in_level, loading::AudioAssets};
 
 use super::create_physical_box;
 use super::ball::{GolfBallBundle, BallState};
 use super::ball::GolfBall;
+use super::goal::Goal;
+use super::spin::{ShotSpin, adjust_spin};
 
 pub struct LauncherPlugin;
 
@@ -18,20 +23,35 @@ impl Plugin for LauncherPlugin {
             .add_event::<LaunchEvent>()
             .register_type::<Launcher>()
             .insert_resource(LaunchVelocity(50.0))
+            .init_resource::<ChargeCurve>()
+            .init_resource::<ControlScheme>()
             .add_system(launcher_added
                 .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(AppState::Playing))
+                .run_if(in_level)
+            )
+            .add_system(sample_launcher_input
+                .in_base_set(CoreSet::PreUpdate)
+                .after(InputManagerSystem::Update)
+                .run_if(in_state(AppState::Playing))
+                .run_if(not(free_flying))
+                .run_if(not(intro_playing))
             )
             .add_system(ball_stopped
-                .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(AppState::Playing))
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
+                .run_if(in_state(GameState::InProgress))
+                .run_if(in_state(AppState::Playing))
             )
             .add_system(play_launch_sound.run_if(on_event::<LaunchEvent>()))
-            .add_system(launch_ball)
-            .add_system(aim_launcher
-                // .in_set(OnUpdate(BallState::Aiming))
+            .add_systems((aim_launcher, adjust_spin, charge_shot, launch_ball)
+                .chain()
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PrePhysics)
             )
-            .add_system(launch_countdown);
+            .add_system(launch_countdown
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PrePhysics)
+            );
     }
 }
 
@@ -42,35 +62,182 @@ pub(crate) struct Launcher;
 #[derive(Resource)]
 pub struct LaunchVelocity(pub f32);
 
+/// Upper bound of the launch velocity setting
+pub const MAX_LAUNCH_VELOCITY: f32 = 100.0;
+/// Holding shoot longer than this gives no extra power
+pub const MAX_CHARGE_SECS: f32 = 2.0;
+/// Weaker shots are dropped like cancelled ones rather than costing a stroke
+const MIN_SHOT_POWER: f32 = 0.02;
+
 pub struct LaunchEvent;
 
+/// Launcher input latched every frame, consumed by the fixed simulation tick
+#[derive(Component, Default)]
+pub(crate) struct LauncherInput {
+    pub(crate) aim: Vec2,
+    pub(crate) spin: Vec2,
+    pub(crate) shoot_held: bool,
+    /// Shoot was released since the last tick
+    pub(crate) shoot_released: bool,
+    /// Cancel was pressed since the last tick
+    pub(crate) cancel_pressed: bool,
+    /// Slingshot pull in world space, points where the ball goes, length 0 to 1 of full power
+    pub(crate) pull: Option<Vec3>,
+}
+
+#[derive(Component, Default)]
+pub(crate) struct ShotCharge {
+    /// Simulation time shoot has been held for
+    pub(crate) held: f32,
+    /// The current press was cancelled and won't fire when released
+    pub(crate) cancelled: bool,
+    /// Slingshot pull from 0 to 1, replaces the charge curve
+    pub(crate) pull: Option<f32>,
+}
+
+impl ShotCharge {
+    pub(crate) fn is_charging(&self) -> bool {
+        self.held > 0.0 && !self.cancelled
+    }
+
+    /// Launch power from 0 to `MAX_CHARGE_SECS`
+    pub(crate) fn power(&self, curve: ChargeCurve) -> f32 {
+        if let Some(pull) = self.pull {
+            return pull.clamp(0.0, 1.0) * MAX_CHARGE_SECS;
+        }
+        let t = self.held / MAX_CHARGE_SECS;
+        let fraction = match curve {
+            ChargeCurve::Linear => t.min(1.0),
+            ChargeCurve::EaseIn => t.min(1.0).powi(2),
+            // Fills and drains for as long as shoot is held, release at the top
+            ChargeCurve::Oscillating => 1.0 - (t % 2.0 - 1.0).abs(),
+        };
+        fraction * MAX_CHARGE_SECS
+    }
+}
+
+/// How shot power grows while shoot is held
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ChargeCurve {
+    #[default]
+    Linear,
+    EaseIn,
+    /// Golf swing style meter that goes up and down
+    Oscillating,
+}
+
+impl ChargeCurve {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChargeCurve::Linear => "Linear",
+            ChargeCurve::EaseIn => "Ease in",
+            ChargeCurve::Oscillating => "Swing",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ChargeCurve::Linear => ChargeCurve::EaseIn,
+            ChargeCurve::EaseIn => ChargeCurve::Oscillating,
+            ChargeCurve::Oscillating => ChargeCurve::Linear,
+        }
+    }
+}
+
+/// How the player aims and shoots
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ControlScheme {
+    /// Hold and release shoot, aim with the stick or arrow keys
+    #[default]
+    Buttons,
+    /// Click or touch and drag back from the launcher, release to fire
+    Slingshot,
+}
+
+impl ControlScheme {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ControlScheme::Buttons => "Buttons",
+            ControlScheme::Slingshot => "Slingshot",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ControlScheme::Buttons => ControlScheme::Slingshot,
+            ControlScheme::Slingshot => ControlScheme::Buttons,
+        }
+    }
+}
+
+/// Aim angles of one launcher in radians
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct LauncherAim {
+    pub(crate) yaw: f32,
+    pub(crate) pitch: f32,
+    /// Heading the yaw limits are measured from
+    pub(crate) center_yaw: f32,
+}
+
+impl LauncherAim {
+    pub(crate) fn from_rotation(rotation: Quat) -> Self {
+        let (yaw, pitch, _) = rotation.to_euler(EulerRot::YXZ);
+        Self { yaw, pitch, center_yaw: yaw }
+    }
+
+    pub(crate) fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(self.pitch)
+    }
+
+    fn clamp(&mut self, settings: &LevelSettings) {
+        self.pitch = self.pitch.clamp(settings.min_pitch.to_radians(), settings.max_pitch.to_radians());
+        if settings.yaw_range < 180.0 {
+            let range = settings.yaw_range.to_radians();
+            self.yaw = self.center_yaw + wrap_angle(self.yaw - self.center_yaw).clamp(-range, range);
+        }
+    }
+}
+
+// Into -PI..PI
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+/// Yaw that points the launcher forward from `from` towards `to`
+pub(crate) fn yaw_towards(from: Vec3, to: Vec3) -> f32 {
+    let direction = to - from;
+    f32::atan2(-direction.x, -direction.z)
+}
+
 // On launcher added
 fn launcher_added(
     query: Query<(Entity, &Transform), Added<Launcher>>,
+    q_settings: Query<&LevelSettings>,
+    bindings: Res<Bindings>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     if let Ok((entity, transform)) = query.get_single() {
         let box_dims = create_physical_box(1., 1., 3.);
+        // Start from the saved orientation
+        let mut aim = LauncherAim::from_rotation(transform.rotation);
+        aim.clamp(&q_settings.get_single().cloned().unwrap_or_default());
+        let bound_actions = BoundActions(vec![Action::Aim, Action::Shoot, Action::CancelShot, Action::Spin]);
 
         commands.entity(entity).insert((
             meshes.add(Mesh::try_from(box_dims.1).unwrap()),
             materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
             SpatialBundle {
-                transform: Transform::from_translation(transform.translation),
+                transform: Transform::from_translation(transform.translation).with_rotation(aim.rotation()),
                 ..default()
             },
-            InputManagerBundle {
-                action_state: ActionState::default(),
-                input_map: InputMap::default()
-                    .insert(DualAxis::left_stick(), Action::Aim)
-                    // .insert(VirtualDPad::wasd(), Action::Aim)
-                    .insert(VirtualDPad::arrow_keys(), Action::Aim)
-                    .insert(KeyCode::Space, Action::Shoot)
-                    .insert(GamepadButtonType::South, Action::Shoot)
-                    .build(),
-            },
+            bound_actions.input_manager_bundle(&bindings),
+            bound_actions,
+            LauncherInput::default(),
+            ShotCharge::default(),
+            ShotSpin::default(),
+            aim,
             Focus,
             Launcher,
             Name::new("Launcher"),
@@ -78,34 +245,95 @@ fn launcher_added(
     }
 }
 
+pub(crate) fn sample_launcher_input(
+    mut query: Query<(&ActionState<Action>, &mut LauncherInput), With<Launcher>>,
+    control_scheme: Res<ControlScheme>,
+) {
+    for (action_state, mut input) in query.iter_mut() {
+        input.aim = action_state.clamped_axis_pair(Action::Aim).map_or(Vec2::ZERO, |axis_pair| axis_pair.xy());
+        input.spin = action_state.clamped_axis_pair(Action::Spin).map_or(Vec2::ZERO, |axis_pair| axis_pair.xy());
+        // Slingshot shots are sampled in `slingshot.rs`
+        if *control_scheme == ControlScheme::Buttons {
+            input.shoot_held = action_state.pressed(Action::Shoot);
+            if action_state.just_released(Action::Shoot) {
+                input.shoot_released = true;
+            }
+        }
+        if action_state.just_pressed(Action::CancelShot) {
+            input.cancel_pressed = true;
+        }
+    }
+}
+
 fn aim_launcher(
-    mut query: Query<(&mut Transform, &ActionState<Action>), With<Launcher>>,
-    time: Res<Time>,
-    mut rotation: Local<Vec2>,
+    mut query: Query<(&mut Transform, &LauncherInput, &mut LauncherAim), With<Launcher>>,
+    q_settings: Query<&LevelSettings>,
+    fixed_time: Res<FixedTime>,
 ) {
     let sensitivity = 1.0;
-    if let Ok((mut trans, action_state)) = query.get_single_mut() { 
-        let axis_pair = action_state.clamped_axis_pair(Action::Aim).unwrap();
+    let dt = fixed_time.period.as_secs_f32();
+    let settings = q_settings.get_single().cloned().unwrap_or_default();
+    for (mut trans, input, mut aim) in query.iter_mut() {
+        aim.pitch += sensitivity * input.aim.y * dt;
+        aim.yaw -= sensitivity * input.aim.x * dt;
+        // Slingshot sets the heading and pitch directly
+        if let Some(pull) = input.pull.filter(|pull| pull.x != 0.0 || pull.z != 0.0) {
+            aim.yaw = yaw_towards(Vec3::ZERO, pull);
+            aim.pitch = f32::atan2(pull.y, Vec2::new(pull.x, pull.z).length());
+        }
+        aim.clamp(&settings);
 
-        rotation.y = sensitivity * axis_pair.y() * time.delta_seconds() + rotation.y;
-        rotation.x = sensitivity * -axis_pair.x() * time.delta_seconds() + rotation.x;
+        let rotation = aim.rotation();
+        if trans.rotation != rotation {
+            trans.rotation = rotation;
+        }
+    }
+}
 
-        trans.rotation = Quat::from_rotation_y(rotation.x) * Quat::from_rotation_x(rotation.y);
+fn charge_shot(
+    mut query: Query<(&mut LauncherInput, &mut ShotCharge), With<Launcher>>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (mut input, mut charge) in query.iter_mut() {
+        if input.cancel_pressed {
+            input.cancel_pressed = false;
+            if charge.is_charging() {
+                info!("Shot cancelled!");
+                charge.cancelled = true;
+            }
+        }
+        if input.shoot_held && !charge.cancelled {
+            charge.held += fixed_time.period.as_secs_f32();
+        }
+        // Also read on release, the last pull before it may have moved into the dead zone
+        if let Some(pull) = input.pull.filter(|_| !charge.cancelled) {
+            charge.pull = Some(pull.length());
+        }
     }
 }
 
 fn launch_ball(
     mut commands: Commands,
-    launcher_q: Query<(&Transform, &ActionState<Action>, Entity), With<Launcher>>,
+    mut launcher_q: Query<(&Transform, &mut LauncherInput, &mut ShotCharge, &ShotSpin, Entity), With<Launcher>>,
     ball_q: Query<Entity, With<GolfBall>>,
     launc_vel: Res<LaunchVelocity>,
+    charge_curve: Res<ChargeCurve>,
     mut launch_event: EventWriter<LaunchEvent>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut ball_state: ResMut<NextState<BallState>>,
 ) {
-    if let Ok((launcher_trans, action_state, launcher_entity)) = launcher_q.get_single() {
-        if action_state.just_released(Action::Shoot) {
+    if let Ok((launcher_trans, mut input, mut charge, spin, launcher_entity)) = launcher_q.get_single_mut() {
+        if input.shoot_released {
+            input.shoot_released = false;
+            input.pull = None;
+            let power = charge.power(*charge_curve);
+            let cancelled = charge.cancelled;
+            *charge = ShotCharge::default();
+            if cancelled || power < MIN_SHOT_POWER {
+                return;
+            }
+
             // Despawn other balls
             for ball in ball_q.iter() { commands.entity(ball).despawn_recursive(); }
 
@@ -121,9 +349,9 @@ fn launch_ball(
             }).insert(Focus)
             .id();
 
-            let velocity = launcher_trans.forward() * launc_vel.0 * action_state.previous_duration(Action::Shoot).as_secs_f32().min(2.0);
+            let velocity = launcher_trans.forward() * launc_vel.0 * power;
         
-            commands.entity(ball).insert( Velocity{ linvel: velocity, angvel: Vec3::ZERO });
+            commands.entity(ball).insert( Velocity{ linvel: velocity, angvel: spin.angvel(launcher_trans) });
             launch_event.send(LaunchEvent);
             ball_state.set(BallState::InPlay);
 
@@ -141,7 +369,8 @@ fn launch_ball(
 fn ball_stopped (
     mut commands: Commands,
     ball_q: Query<(Entity, &Transform), With<GolfBall>>,
-    launcher_q: Query<Entity, With<Launcher>>,
+    launcher_q: Query<(Entity, Option<&LauncherAim>), With<Launcher>>,
+    goal_q: Query<&Transform, With<Goal>>,
     level_q: Query<Entity, With<Level>>,
     mut last_pos: Local<Vec3>,
     mut ball_state: ResMut<NextState<BallState>>,
@@ -151,13 +380,26 @@ fn ball_stopped (
         if transform.translation.distance_squared(*last_pos) < 0.00001 {
             info!("Stopped ball!");
 
-            for entity in launcher_q.iter() {
+            // Keep the pitch of the last shot
+            let mut pitch = 0.0;
+            for (entity, aim) in launcher_q.iter() {
+                pitch = aim.map_or(pitch, |aim| aim.pitch);
                 commands.entity(entity).despawn_recursive();
             }
+            // Face the nearest goal
+            let yaw = goal_q.iter()
+                .map(|goal| goal.translation)
+                .min_by(|a, b| a.distance_squared(transform.translation).total_cmp(&b.distance_squared(transform.translation)))
+                .map_or(0.0, |goal| yaw_towards(transform.translation, goal));
+            let aim = LauncherAim { yaw, pitch, center_yaw: yaw };
+
             let level = level_q.single();
             let launcher = commands.spawn((
                 Launcher,
-                SpatialBundle { transform: *transform, ..default() },
+                SpatialBundle {
+                    transform: Transform::from_translation(transform.translation).with_rotation(aim.rotation()),
+                    ..default()
+                },
             )).id();
             ball_state.set(BallState::Aiming);
             commands.entity(level).add_child(launcher);
@@ -208,12 +450,12 @@ pub(crate) struct LaunchTimer(pub Timer);
 // Launch ball after one sec
 fn launch_countdown(
     mut commands: Commands,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
     mut query: Query<(Entity, &mut LaunchTimer)>,
     mut q_locked_axes: Query<&mut LockedAxes>,
 ) {
     for (entity, mut timer) in &mut query {
-        if timer.tick(time.delta()).finished() {
+        if timer.tick(fixed_time.period).finished() {
             info!("Launch!");
             let mut axes = q_locked_axes.get_mut(entity).unwrap();
             axes.toggle(LockedAxes::all());
@@ -223,3 +465,32 @@ fn launch_countdown(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(secs: f32) -> ShotCharge {
+        ShotCharge { held: secs, ..default() }
+    }
+
+    #[test]
+    fn linear_power_grows_with_hold_time_and_caps() {
+        assert_eq!(held(0.0).power(ChargeCurve::Linear), 0.0);
+        assert_eq!(held(MAX_CHARGE_SECS / 2.0).power(ChargeCurve::Linear), MAX_CHARGE_SECS / 2.0);
+        assert_eq!(held(MAX_CHARGE_SECS * 3.0).power(ChargeCurve::Linear), MAX_CHARGE_SECS);
+    }
+
+    #[test]
+    fn ease_in_power_starts_slow_and_caps() {
+        assert_eq!(held(MAX_CHARGE_SECS / 2.0).power(ChargeCurve::EaseIn), MAX_CHARGE_SECS / 4.0);
+        assert_eq!(held(MAX_CHARGE_SECS * 3.0).power(ChargeCurve::EaseIn), MAX_CHARGE_SECS);
+    }
+
+    #[test]
+    fn oscillating_power_fills_and_drains() {
+        assert_eq!(held(MAX_CHARGE_SECS).power(ChargeCurve::Oscillating), MAX_CHARGE_SECS);
+        assert_eq!(held(MAX_CHARGE_SECS * 1.5).power(ChargeCurve::Oscillating), MAX_CHARGE_SECS / 2.0);
+        assert_eq!(held(MAX_CHARGE_SECS * 2.0).power(ChargeCurve::Oscillating), 0.0);
+    }
+}
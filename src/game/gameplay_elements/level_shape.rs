@@ -0,0 +1,176 @@
+use std::f32::consts::FRAC_PI_2;
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use bevy_rapier3d::prelude::*;
+
+use super::wall;
+
+pub struct ShapePlugin;
+
+impl Plugin for ShapePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Shape>();
+    }
+}
+
+/// Level geometry shape, all shapes are centered on the entity
+#[derive(Component, Reflect, FromReflect, Clone, Copy, PartialEq, Debug)]
+#[reflect(Component)]
+pub(crate) enum Shape {
+    Cuboid { x: f32, y: f32, z: f32 },
+    /// Ramp rising from the front (+Z) to full height at the back (-Z)
+    Wedge { x: f32, y: f32, z: f32 },
+    Cylinder { radius: f32, height: f32 },
+    Sphere { radius: f32 },
+    /// Capsule along Y, `length` is the cylindrical part between the caps
+    Capsule { radius: f32, length: f32 },
+    /// Concave ramp curving from flat at the front (+Z) to vertical at the back (-Z)
+    QuarterPipe { radius: f32, width: f32 },
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape::Cuboid { x: 1.0, y: 1.0, z: 1.0 }
+    }
+}
+
+impl From<&wall::Box> for Shape {
+    fn from(box_dims: &wall::Box) -> Self {
+        Shape::Cuboid { x: box_dims.x, y: box_dims.y, z: box_dims.z }
+    }
+}
+
+const QUARTER_PIPE_SEGMENTS: usize = 16;
+
+impl Shape {
+    pub(crate) fn collider(&self) -> Collider {
+        match *self {
+            Shape::Cuboid { x, y, z } => Collider::cuboid(x/2., y/2., z/2.),
+            Shape::Wedge { x, y, z } => Collider::convex_hull(&wedge_points(x, y, z))
+                .unwrap_or_else(|| Collider::cuboid(x/2., y/2., z/2.)),
+            Shape::Cylinder { radius, height } => Collider::cylinder(height/2., radius),
+            Shape::Sphere { radius } => Collider::ball(radius),
+            Shape::Capsule { radius, length } => Collider::capsule_y(length/2., radius),
+            Shape::QuarterPipe { radius, width } => {
+                // Concave, so built from one convex slice per curve segment
+                let curve = quarter_pipe_curve(radius);
+                let corner = Vec2::new(-radius/2., -radius/2.);
+                let slices = curve.windows(2)
+                    .filter_map(|pair| {
+                        let points: Vec<Vec3> = [pair[0], pair[1], corner].iter()
+                            .flat_map(|p| [Vec3::new(-width/2., p.y, p.x), Vec3::new(width/2., p.y, p.x)])
+                            .collect();
+                        Collider::convex_hull(&points)
+                    })
+                    .map(|slice| (Vec3::ZERO, Quat::IDENTITY, slice))
+                    .collect();
+                Collider::compound(slices)
+            },
+        }
+    }
+
+    pub(crate) fn mesh(&self) -> Mesh {
+        match *self {
+            Shape::Cuboid { x, y, z } => Mesh::from(shape::Box::new(x, y, z)),
+            Shape::Wedge { x, y, z } => {
+                let [a, b, c, d, e, f] = wedge_points(x, y, z);
+                flat_mesh(&[
+                    [a, b, c], [a, c, d], // Bottom
+                    [a, f, b], [a, e, f], // Back
+                    [d, c, f], [d, f, e], // Slope
+                    [a, d, e], // Left
+                    [b, f, c], // Right
+                ])
+            },
+            Shape::Cylinder { radius, height } => Mesh::from(shape::Cylinder { radius, height, ..default() }),
+            Shape::Sphere { radius } => Mesh::try_from(shape::Icosphere { radius, subdivisions: 5 }).unwrap(),
+            Shape::Capsule { radius, length } => Mesh::from(shape::Capsule { radius, depth: length, ..default() }),
+            Shape::QuarterPipe { radius, width } => {
+                let curve = quarter_pipe_curve(radius);
+                let left = |p: Vec2| Vec3::new(-width/2., p.y, p.x);
+                let right = |p: Vec2| Vec3::new(width/2., p.y, p.x);
+                let corner = Vec2::new(-radius/2., -radius/2.);
+                let (first, last) = (curve[0], curve[QUARTER_PIPE_SEGMENTS]);
+
+                let mut triangles = vec![
+                    // Bottom and back
+                    [left(corner), right(first), left(first)], [left(corner), right(corner), right(first)],
+                    [left(corner), right(last), right(corner)], [left(corner), left(last), right(last)],
+                ];
+                for pair in curve.windows(2) {
+                    let (p0, p1) = (pair[0], pair[1]);
+                    triangles.push([left(p0), right(p0), right(p1)]);
+                    triangles.push([left(p0), right(p1), left(p1)]);
+                    triangles.push([left(corner), left(p0), left(p1)]);
+                    triangles.push([right(corner), right(p1), right(p0)]);
+                }
+                flat_mesh(&triangles)
+            },
+        }
+    }
+}
+
+// Bottom corners a, b, c, d then the top back edge e, f
+fn wedge_points(x: f32, y: f32, z: f32) -> [Vec3; 6] {
+    let (x, y, z) = (x/2., y/2., z/2.);
+    [
+        Vec3::new(-x, -y, -z), Vec3::new(x, -y, -z), Vec3::new(x, -y, z), Vec3::new(-x, -y, z),
+        Vec3::new(-x, y, -z), Vec3::new(x, y, -z),
+    ]
+}
+
+// Curve profile in (z, y), from the bottom front to the top back of the bounding square
+fn quarter_pipe_curve(radius: f32) -> Vec<Vec2> {
+    let center = Vec2::new(radius/2., radius/2.);
+    (0..=QUARTER_PIPE_SEGMENTS)
+        .map(|i| {
+            let angle = FRAC_PI_2 * i as f32 / QUARTER_PIPE_SEGMENTS as f32;
+            center - radius * Vec2::new(angle.sin(), angle.cos())
+        })
+        .collect()
+}
+
+// Counter clockwise triangles, flat shaded
+fn flat_mesh(triangles: &[[Vec3; 3]]) -> Mesh {
+    let positions: Vec<[f32; 3]> = triangles.iter().flatten().map(|p| p.to_array()).collect();
+    let uvs = vec![[0., 0.]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.compute_flat_normals();
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+    use super::*;
+
+    #[test]
+    fn box_becomes_cuboid() {
+        let shape = Shape::from(&wall::Box { x: 1.0, y: 2.0, z: 3.0 });
+        assert_eq!(shape, Shape::Cuboid { x: 1.0, y: 2.0, z: 3.0 });
+    }
+
+    #[test]
+    fn quarter_pipe_curve_spans_front_bottom_to_back_top() {
+        let curve = quarter_pipe_curve(4.0);
+        assert_eq!(curve.len(), QUARTER_PIPE_SEGMENTS + 1);
+        assert!(curve[0].abs_diff_eq(Vec2::new(2.0, -2.0), 1e-5));
+        assert!(curve[QUARTER_PIPE_SEGMENTS].abs_diff_eq(Vec2::new(-2.0, 2.0), 1e-5));
+    }
+
+    #[test]
+    fn wedge_faces_point_outwards() {
+        let points = wedge_points(2.0, 1.0, 3.0);
+        let inside = points.iter().sum::<Vec3>() / points.len() as f32;
+
+        let mesh = Shape::Wedge { x: 2.0, y: 1.0, z: 3.0 }.mesh();
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else { panic!() };
+        let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) else { panic!() };
+        assert_eq!(positions.len(), 8 * 3);
+        for (position, normal) in positions.iter().zip(normals.iter()) {
+            assert!(Vec3::from(*normal).dot(Vec3::from(*position) - inside) > 0.0);
+        }
+    }
+}
@@ -3,12 +3,19 @@ pub mod ball;
 pub mod goal;
 pub mod wall;
 pub mod death_zone;
+pub mod status_effect;
+pub mod level_shape;
+pub mod terrain;
+pub mod trajectory;
+pub mod power_meter;
+pub mod slingshot;
+pub mod spin;
 
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use crate::AppState;
 
-use self::{ball::GolfBallPlugin, death_zone::{DeathZone, cleanup_death_zone, add_death_zone, DeathZonePlugin}, wall::WallPlugin, goal::GoalPlugin};
+use self::{ball::GolfBallPlugin, death_zone::{DeathZone, cleanup_death_zone, add_death_zone, DeathZonePlugin}, wall::WallPlugin, goal::GoalPlugin, status_effect::StatusEffectPlugin, level_shape::ShapePlugin, terrain::TerrainPlugin, trajectory::TrajectoryPlugin, power_meter::PowerMeterPlugin, slingshot::SlingshotPlugin, spin::SpinPlugin};
 pub use self::launcher::{LauncherPlugin, LaunchEvent};
 
 pub struct GameplayElementsPlugin;
@@ -21,6 +28,13 @@ impl Plugin for GameplayElementsPlugin {
             .add_plugin(GoalPlugin)
             .add_plugin(WallPlugin)
             .add_plugin(DeathZonePlugin)
+            .add_plugin(StatusEffectPlugin)
+            .add_plugin(ShapePlugin)
+            .add_plugin(TerrainPlugin)
+            .add_plugin(TrajectoryPlugin)
+            .add_plugin(PowerMeterPlugin)
+            .add_plugin(SlingshotPlugin)
+            .add_plugin(SpinPlugin)
             .register_type::<DeathZone>()
             .add_system(add_death_zone.in_schedule(OnEnter(AppState::Playing)))
             .add_system(cleanup_death_zone.in_schedule(OnExit(AppState::Playing)))
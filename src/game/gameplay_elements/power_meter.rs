@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+use crate::AppState;
+use super::launcher::{Launcher, ShotCharge, ChargeCurve, MAX_CHARGE_SECS};
+
+pub struct PowerMeterPlugin;
+
+impl Plugin for PowerMeterPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(setup_power_meter.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(update_power_meter.in_set(OnUpdate(AppState::Playing)))
+            ;
+    }
+}
+
+#[derive(Component)]
+struct PowerMeter;
+
+#[derive(Component)]
+struct PowerMeterFill;
+
+fn setup_power_meter(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(40.0),
+                    bottom: Val::Px(40.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(24.0), Val::Px(200.0)),
+                padding: UiRect::all(Val::Px(3.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::FlexEnd,
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.1, 0.1, 0.7).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        PowerMeter,
+        Name::new("Power meter"),
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(0.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            PowerMeterFill,
+        ));
+    });
+}
+
+// Only shown while a shot is being charged
+fn update_power_meter(
+    charge_curve: Res<ChargeCurve>,
+    q_launcher: Query<&ShotCharge, With<Launcher>>,
+    mut q_meter: Query<&mut Visibility, With<PowerMeter>>,
+    mut q_fill: Query<(&mut Style, &mut BackgroundColor), With<PowerMeterFill>>,
+) {
+    let charge = q_launcher.get_single().ok().filter(|charge| charge.is_charging());
+
+    for mut visibility in q_meter.iter_mut() {
+        let target = if charge.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+
+    let Some(charge) = charge else { return };
+    let fraction = charge.power(*charge_curve) / MAX_CHARGE_SECS;
+    for (mut style, mut color) in q_fill.iter_mut() {
+        style.size.height = Val::Percent(fraction * 100.0);
+        // Green at low power to red at full power
+        *color = Color::rgb(0.2 + 0.7 * fraction, 0.8 - 0.6 * fraction, 0.2).into();
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::EguiContext;
+use leafwing_input_manager::plugin::InputManagerSystem;
+use crate::{AppState, camera::{MainCamera, free_flying}, game::level::intro_playing};
+use super::launcher::{Launcher, LauncherInput, ControlScheme, sample_launcher_input};
+
+pub struct SlingshotPlugin;
+
+impl Plugin for SlingshotPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SlingshotDrag>()
+            .add_system(sample_slingshot_input
+                .in_base_set(CoreSet::PreUpdate)
+                .after(InputManagerSystem::Update)
+                .after(sample_launcher_input)
+                .run_if(in_state(AppState::Playing))
+                .run_if(resource_equals(ControlScheme::Slingshot))
+                .run_if(not(free_flying))
+                .run_if(not(intro_playing))
+            )
+            .add_system(setup_slingshot_markers.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(update_slingshot_markers.in_set(OnUpdate(AppState::Playing)))
+            ;
+    }
+}
+
+/// Screen distance to drag back for a full power shot
+const FULL_PULL_PX: f32 = 250.0;
+/// Releasing closer than this to the drag start cancels the shot
+const DEAD_ZONE_PX: f32 = 20.0;
+/// Presses further than this from the launcher on screen don't grab it
+const GRAB_RADIUS_PX: f32 = 80.0;
+/// Launch pitch in degrees for pulling all the way down
+const MAX_PULL_PITCH: f32 = 45.0;
+
+#[derive(Resource, Default)]
+struct SlingshotDrag {
+    start: Option<Vec2>,
+    current: Vec2,
+    /// The pointer was down last frame, only a fresh press starts a drag
+    pressed: bool,
+}
+
+// Touches come first, otherwise the mouse. Both are in window coordinates from the bottom left.
+fn pointer_position(window: &Window, mouse: &Input<MouseButton>, touches: &Touches) -> Option<Vec2> {
+    if let Some(touch) = touches.iter().next() {
+        return Some(touch.position());
+    }
+    if mouse.pressed(MouseButton::Left) {
+        return window.cursor_position();
+    }
+    None
+}
+
+/// Whether egui or a HUD button has the pointer
+fn pointer_over_ui(q_egui: &mut Query<&mut EguiContext, With<PrimaryWindow>>, q_interaction: &Query<&Interaction>) -> bool {
+    q_egui.get_single_mut().map_or(false, |mut egui| egui.get_mut().wants_pointer_input())
+        || q_interaction.iter().any(|interaction| *interaction != Interaction::None)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sample_slingshot_input(
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut q_launcher: Query<(&mut LauncherInput, &GlobalTransform), With<Launcher>>,
+    mut q_egui: Query<&mut EguiContext, With<PrimaryWindow>>,
+    q_interaction: Query<&Interaction>,
+    mouse: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    mut drag: ResMut<SlingshotDrag>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (q_window.get_single(), q_camera.get_single()) else { return };
+    let pointer = pointer_position(window, &mouse, &touches);
+    let just_pressed = pointer.is_some() && !drag.pressed;
+    drag.pressed = pointer.is_some();
+
+    let Ok((mut input, launcher)) = q_launcher.get_single_mut() else {
+        drag.start = None;
+        return;
+    };
+
+    let start = match (drag.start, pointer) {
+        (None, Some(position)) => {
+            // Drags start on the launcher, not anywhere on screen or over the UI
+            let on_launcher = camera.world_to_viewport(camera_transform, launcher.translation())
+                .map_or(false, |launcher| launcher.distance(position) < GRAB_RADIUS_PX);
+            if !just_pressed || !on_launcher || pointer_over_ui(&mut q_egui, &q_interaction) {
+                return;
+            }
+            drag.start = Some(position);
+            drag.current = position;
+            position
+        },
+        (Some(start), Some(position)) => {
+            drag.current = position;
+            start
+        },
+        (Some(start), None) => {
+            // Released, the last pull is the shot, a release in the dead zone has no pull and cancels
+            drag.start = None;
+            input.shoot_held = false;
+            input.shoot_released = true;
+            if start.distance(drag.current) < DEAD_ZONE_PX {
+                input.cancel_pressed = true;
+            }
+            return;
+        },
+        (None, None) => return,
+    };
+
+    // Pulling down and left sends the ball away from the camera and to the right, pulling further down lobs it higher
+    let delta = start - drag.current;
+    let forward = Vec3::new(camera_transform.forward().x, 0.0, camera_transform.forward().z).normalize_or_zero();
+    let right = Vec3::new(camera_transform.right().x, 0.0, camera_transform.right().z).normalize_or_zero();
+
+    input.shoot_held = true;
+    input.pull = Some(if delta.length() < DEAD_ZONE_PX {
+        Vec3::ZERO
+    } else {
+        let heading = (right * delta.x + forward * delta.y).normalize_or_zero();
+        let pitch = (delta.y / FULL_PULL_PX).clamp(0.0, 1.0) * MAX_PULL_PITCH.to_radians();
+        (heading * pitch.cos() + Vec3::Y * pitch.sin()) * (delta.length() / FULL_PULL_PX).min(1.0)
+    });
+}
+
+#[derive(Component)]
+struct SlingshotAnchor;
+
+#[derive(Component)]
+struct SlingshotKnob;
+
+fn marker_bundle(size: f32, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: Size::new(Val::Px(size), Val::Px(size)),
+            ..default()
+        },
+        background_color: color.into(),
+        visibility: Visibility::Hidden,
+        ..default()
+    }
+}
+
+fn setup_slingshot_markers(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        marker_bundle(16.0, Color::rgba(0.9, 0.9, 0.9, 0.8)),
+        SlingshotAnchor,
+        Name::new("Slingshot anchor"),
+    ));
+    commands.spawn((
+        marker_bundle(24.0, Color::rgba(0.8, 0.7, 0.6, 0.8)),
+        SlingshotKnob,
+        Name::new("Slingshot knob"),
+    ));
+}
+
+// Markers at the drag start and the pointer while pulling back
+fn update_slingshot_markers(
+    drag: Res<SlingshotDrag>,
+    control_scheme: Res<ControlScheme>,
+    q_launcher: Query<(), With<Launcher>>,
+    mut q_anchor: Query<(&mut Style, &mut Visibility, &Node), (With<SlingshotAnchor>, Without<SlingshotKnob>)>,
+    mut q_knob: Query<(&mut Style, &mut Visibility, &Node), (With<SlingshotKnob>, Without<SlingshotAnchor>)>,
+) {
+    let anchor = drag.start.filter(|_| *control_scheme == ControlScheme::Slingshot && !q_launcher.is_empty());
+    let positions = [(q_anchor.get_single_mut(), anchor), (q_knob.get_single_mut(), anchor.map(|_| drag.current))];
+    for (marker, position) in positions {
+        let Ok((mut style, mut visibility, node)) = marker else { continue };
+        match position {
+            Some(position) => {
+                let half = node.size() / 2.0;
+                style.position = UiRect {
+                    left: Val::Px(position.x - half.x),
+                    bottom: Val::Px(position.y - half.y),
+                    ..default()
+                };
+                *visibility = Visibility::Inherited;
+            },
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
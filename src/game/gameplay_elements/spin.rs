@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use crate::{AppState, environment::physics::SimulationSet};
+use super::{ball::{GolfBall, BallState, BALL_RADIUS}, launcher::{Launcher, LauncherInput}};
+
+pub struct SpinPlugin;
+
+impl Plugin for SpinPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(magnus_effect
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PrePhysics)
+            )
+            .add_system(spin_landing
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
+            )
+            .add_system(setup_spin_indicator.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(update_spin_indicator.in_set(OnUpdate(AppState::Playing)))
+            ;
+    }
+}
+
+/// Ball angular velocity in rad/s at full spin
+pub(crate) const MAX_SPIN: f32 = 30.0;
+/// Acceleration per unit of `angvel × linvel`
+pub(crate) const MAGNUS_COEFFICIENT: f32 = 0.003;
+/// Full spin from no spin while spin input is held
+const SPIN_ADJUST_SECS: f32 = 1.0;
+/// Share of the spin's rolling speed the ball picks up on landing, and of the spin it loses
+const LANDING_GRIP: f32 = 0.2;
+/// Bounce change at full spin, topspin lands livelier and backspin bites
+const LANDING_SPIN_RESTITUTION: f32 = 0.3;
+/// Sideways speed per rad/s of sidespin on landing
+const LANDING_SIDE_KICK: f32 = 0.1;
+/// Contacts with a flatter normal are walls, not landings
+const LANDING_MIN_UP: f32 = 0.5;
+
+/// Spin given to the next shot, each axis from -1 to 1
+#[derive(Component, Default, Clone, Copy)]
+pub(crate) struct ShotSpin {
+    /// Positive is topspin, negative is backspin
+    pub(crate) top: f32,
+    /// Positive curves right, negative curves left
+    pub(crate) side: f32,
+}
+
+impl ShotSpin {
+    /// Ball angular velocity for a shot fired with `launcher` orientation
+    pub(crate) fn angvel(&self, launcher: &Transform) -> Vec3 {
+        // Topspin rolls the ball over its front, sidespin turns it around the up axis
+        (launcher.left() * self.top + launcher.down() * self.side) * MAX_SPIN
+    }
+}
+
+/// Lift or curve from spin, `angvel × linvel`
+pub(crate) fn magnus_acceleration(angvel: Vec3, linvel: Vec3) -> Vec3 {
+    angvel.cross(linvel) * MAGNUS_COEFFICIENT
+}
+
+/// Velocity and spin after the ball lands on a surface with `normal`, pointing at the ball
+///
+/// Spin rolling the ball along the surface adds to its speed and changes the bounce,
+/// sidespin kicks it sideways.
+pub(crate) fn land(linvel: Vec3, angvel: Vec3, normal: Vec3) -> (Vec3, Vec3) {
+    let bounce = normal * linvel.dot(normal);
+    let along = linvel - bounce;
+    let heading = along.normalize_or_zero();
+
+    // Speed the ball would have rolling with this spin, forward for topspin
+    let roll = angvel.cross(normal) * BALL_RADIUS;
+    let top = (roll.dot(heading) / (MAX_SPIN * BALL_RADIUS)).clamp(-1.0, 1.0);
+    let side = normal * angvel.dot(normal);
+
+    let bounce = bounce * (1.0 + LANDING_SPIN_RESTITUTION * top);
+    let along = along + roll * LANDING_GRIP + side.cross(heading) * LANDING_SIDE_KICK;
+    (bounce + along, angvel * (1.0 - LANDING_GRIP))
+}
+
+/// Whether the ball touched anything after the last physics step
+#[derive(Component, Default)]
+pub(crate) struct BallContact {
+    touching: bool,
+}
+
+// Normal of the first surface the ball touches, pointing at the ball
+fn contact_normal(rapier_context: &RapierContext, ball: Entity) -> Option<Vec3> {
+    rapier_context.contacts_with(ball)
+        .filter(|pair| pair.has_any_active_contacts())
+        .find_map(|pair| {
+            let normal = pair.manifolds().next()?.normal();
+            Some(if pair.collider1() == ball { -normal } else { normal })
+        })
+}
+
+pub(crate) fn adjust_spin(
+    mut query: Query<(&LauncherInput, &mut ShotSpin), With<Launcher>>,
+    fixed_time: Res<FixedTime>,
+) {
+    let step = fixed_time.period.as_secs_f32() / SPIN_ADJUST_SECS;
+    for (input, mut spin) in query.iter_mut() {
+        if input.spin == Vec2::ZERO {
+            continue;
+        }
+        spin.top = (spin.top + input.spin.y * step).clamp(-1.0, 1.0);
+        spin.side = (spin.side + input.spin.x * step).clamp(-1.0, 1.0);
+    }
+}
+
+// Spin only curves the ball in the air
+fn magnus_effect(
+    mut query: Query<(&mut Velocity, &BallContact), With<GolfBall>>,
+    fixed_time: Res<FixedTime>,
+) {
+    let dt = fixed_time.period.as_secs_f32();
+    for (mut velocity, contact) in query.iter_mut() {
+        if contact.touching {
+            continue;
+        }
+        let acceleration = magnus_acceleration(velocity.angvel, velocity.linvel);
+        if acceleration != Vec3::ZERO {
+            velocity.linvel += acceleration * dt;
+        }
+    }
+}
+
+fn spin_landing(
+    rapier_context: Res<RapierContext>,
+    mut query: Query<(Entity, &mut Velocity, &mut BallContact), With<GolfBall>>,
+) {
+    for (ball, mut velocity, mut contact) in query.iter_mut() {
+        let normal = contact_normal(&rapier_context, ball);
+        match normal {
+            Some(normal) if !contact.touching && normal.y >= LANDING_MIN_UP => {
+                (velocity.linvel, velocity.angvel) = land(velocity.linvel, velocity.angvel, normal);
+            },
+            _ => (),
+        }
+        contact.touching = normal.is_some();
+    }
+}
+
+#[derive(Component)]
+struct SpinIndicator;
+
+/// Where the club hits the ball, top of the ball for topspin, left side to curve right
+#[derive(Component)]
+struct SpinContactPoint;
+
+const INDICATOR_SIZE: f32 = 60.0;
+const CONTACT_POINT_SIZE: f32 = 12.0;
+
+fn setup_spin_indicator(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(80.0),
+                    bottom: Val::Px(40.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(INDICATOR_SIZE), Val::Px(INDICATOR_SIZE)),
+                ..default()
+            },
+            background_color: Color::rgba(0.8, 0.7, 0.6, 0.7).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        SpinIndicator,
+        Name::new("Spin indicator"),
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Px(CONTACT_POINT_SIZE), Val::Px(CONTACT_POINT_SIZE)),
+                    ..default()
+                },
+                background_color: Color::rgb(0.8, 0.1, 0.1).into(),
+                ..default()
+            },
+            SpinContactPoint,
+        ));
+    });
+}
+
+// Shown while aiming
+fn update_spin_indicator(
+    ball_state: Res<State<BallState>>,
+    q_launcher: Query<&ShotSpin, With<Launcher>>,
+    mut q_indicator: Query<&mut Visibility, With<SpinIndicator>>,
+    mut q_point: Query<&mut Style, With<SpinContactPoint>>,
+) {
+    let spin = q_launcher.get_single().ok().filter(|_| ball_state.0 == BallState::Aiming);
+    for mut visibility in q_indicator.iter_mut() {
+        let target = if spin.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+
+    let Some(spin) = spin else { return };
+    let travel = (INDICATOR_SIZE - CONTACT_POINT_SIZE) / 2.0;
+    for mut style in q_point.iter_mut() {
+        style.position = UiRect {
+            left: Val::Px(travel - spin.side * travel),
+            bottom: Val::Px(travel + spin.top * travel),
+            ..default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORWARD: Vec3 = Vec3::NEG_Z;
+
+    fn landed(angvel: Vec3) -> Vec3 {
+        land(Vec3::new(0.0, 5.0, -10.0), angvel, Vec3::Y).0
+    }
+
+    #[test]
+    fn no_spin_lands_unchanged() {
+        assert_eq!(land(Vec3::new(0.0, 5.0, -10.0), Vec3::ZERO, Vec3::Y), (Vec3::new(0.0, 5.0, -10.0), Vec3::ZERO));
+    }
+
+    #[test]
+    fn topspin_runs_on_and_bounces_higher() {
+        let spin = ShotSpin { top: 1.0, side: 0.0 }.angvel(&Transform::IDENTITY);
+        let velocity = landed(spin);
+        assert!(velocity.dot(FORWARD) > 10.0);
+        assert!(velocity.y > 5.0);
+    }
+
+    #[test]
+    fn backspin_checks_and_bites() {
+        let spin = ShotSpin { top: -1.0, side: 0.0 }.angvel(&Transform::IDENTITY);
+        let velocity = landed(spin);
+        assert!(velocity.dot(FORWARD) < 10.0);
+        assert!(velocity.y < 5.0);
+    }
+
+    #[test]
+    fn sidespin_kicks_the_way_it_curves() {
+        let spin = ShotSpin { top: 0.0, side: 1.0 }.angvel(&Transform::IDENTITY);
+        assert!(landed(spin).x > 0.0);
+        assert!(magnus_acceleration(spin, FORWARD).x > 0.0);
+        assert_eq!(landed(spin).y, 5.0);
+    }
+
+    #[test]
+    fn landing_uses_up_some_spin() {
+        let spin = ShotSpin { top: 1.0, side: 1.0 }.angvel(&Transform::IDENTITY);
+        let (_, angvel) = land(Vec3::new(0.0, 5.0, -10.0), spin, Vec3::Y);
+        assert!(angvel.length() < spin.length());
+    }
+}
@@ -0,0 +1,448 @@
+use std::time::Duration;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use crate::{AppState, loading::FontAssets, environment::physics::SimulationSet, game::level::level_manager::LoadLevelEvent};
+use super::ball::{GolfBall, BallState};
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<EffectKind>()
+            .register_type::<EffectStacking>()
+            .register_type::<StatusEffect>()
+            .register_type::<Vec<StatusEffect>>()
+            .register_type::<EffectGiver>()
+            .add_system(effect_giver_added)
+            .add_systems((effect_giver_collision, tick_status_effects, apply_status_effects, speed_boost_thrust)
+                .chain()
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
+            )
+            .add_system(clear_status_effects.in_schedule(OnEnter(BallState::Dead)))
+            .add_system(clear_status_effects.in_schedule(OnEnter(BallState::InPlay)))
+            .add_system(clear_status_effects.run_if(on_event::<LoadLevelEvent>()))
+            .add_system(setup_status_effect_hud.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(update_status_effect_hud.in_set(OnUpdate(AppState::Playing)))
+            ;
+    }
+}
+
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub(crate) enum EffectKind {
+    /// Gravity scale is multiplied by the magnitude
+    #[default]
+    LowGravity,
+    /// Pushes the ball along its velocity with a force of magnitude
+    SpeedBoost,
+    /// Adds magnitude to the ball's linear and angular damping
+    Sticky,
+    /// Gravity scale is multiplied by the magnitude
+    Heavy,
+    /// Restitution is replaced by the magnitude
+    NoBounce,
+}
+
+impl EffectKind {
+    fn label(&self) -> &'static str {
+        match self {
+            EffectKind::LowGravity => "LOW G",
+            EffectKind::SpeedBoost => "BOOST",
+            EffectKind::Sticky => "STICKY",
+            EffectKind::Heavy => "HEAVY",
+            EffectKind::NoBounce => "NO BOUNCE",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            EffectKind::LowGravity => Color::rgb(0.2, 0.2, 0.8),
+            EffectKind::SpeedBoost => Color::rgb(0.8, 0.5, 0.1),
+            EffectKind::Sticky => Color::rgb(0.4, 0.6, 0.1),
+            EffectKind::Heavy => Color::rgb(0.4, 0.4, 0.4),
+            EffectKind::NoBounce => Color::rgb(0.6, 0.1, 0.1),
+        }
+    }
+}
+
+/// What granting an effect does while the same kind of effect is already active
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum EffectStacking {
+    /// Restart the effect with the new values
+    #[default]
+    Refresh,
+    /// Add the new duration to the time left
+    Extend,
+    /// Keep the running effect untouched
+    Ignore,
+}
+
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Debug)]
+pub(crate) struct StatusEffect {
+    pub(crate) kind: EffectKind,
+    pub(crate) duration: f32,
+    pub(crate) magnitude: f32,
+    pub(crate) stacking: EffectStacking,
+}
+
+impl Default for StatusEffect {
+    fn default() -> Self {
+        Self { kind: EffectKind::LowGravity, duration: 10.0, magnitude: 0.1, stacking: EffectStacking::Refresh }
+    }
+}
+
+/// Grants its effects to the ball when the ball leaves contact with this entity
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct EffectGiver {
+    pub(crate) effects: Vec<StatusEffect>,
+}
+
+struct ActiveEffect {
+    kind: EffectKind,
+    magnitude: f32,
+    timer: Timer,
+}
+
+/// Ball physics values from before any effect was applied
+#[derive(Clone, Copy)]
+struct BallPhysics {
+    gravity_scale: f32,
+    damping: Damping,
+    restitution: Restitution,
+}
+
+impl BallPhysics {
+    fn from_components(gravity_scale: Option<&GravityScale>, damping: Option<&Damping>, restitution: Option<&Restitution>) -> Self {
+        Self {
+            gravity_scale: gravity_scale.map_or(1.0, |scale| scale.0),
+            damping: damping.copied().unwrap_or_default(),
+            restitution: restitution.copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub(crate) struct StatusEffects {
+    active: Vec<ActiveEffect>,
+    base: Option<BallPhysics>,
+    dirty: bool,
+}
+
+impl StatusEffects {
+    pub(crate) fn grant(&mut self, effect: &StatusEffect) {
+        let duration = Duration::from_secs_f32(effect.duration);
+        match self.active.iter_mut().find(|active| active.kind == effect.kind) {
+            Some(_) if effect.stacking == EffectStacking::Ignore => return,
+            Some(active) => {
+                let duration = match effect.stacking {
+                    EffectStacking::Extend => active.timer.remaining() + duration,
+                    _ => duration,
+                };
+                active.magnitude = effect.magnitude;
+                active.timer = Timer::new(duration, TimerMode::Once);
+            },
+            None => self.active.push(ActiveEffect {
+                kind: effect.kind,
+                magnitude: effect.magnitude,
+                timer: Timer::new(duration, TimerMode::Once),
+            }),
+        }
+        info!("Effect {:?}!", effect.kind);
+        self.dirty = true;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        if !self.active.is_empty() {
+            self.active.clear();
+            self.dirty = true;
+        }
+    }
+
+    /// Gravity scale and damping the ball flies with, from its current components
+    pub(crate) fn flight(&self, gravity_scale: Option<&GravityScale>, damping: Option<&Damping>) -> (f32, Damping) {
+        let base = self.base.unwrap_or_else(|| BallPhysics::from_components(gravity_scale, damping, None));
+        let physics = self.physics(base);
+        (physics.gravity_scale, physics.damping)
+    }
+
+    fn physics(&self, base: BallPhysics) -> BallPhysics {
+        let mut physics = base;
+        for active in self.active.iter() {
+            match active.kind {
+                EffectKind::LowGravity | EffectKind::Heavy => physics.gravity_scale *= active.magnitude,
+                EffectKind::Sticky => {
+                    physics.damping.linear_damping += active.magnitude;
+                    physics.damping.angular_damping += active.magnitude;
+                },
+                EffectKind::NoBounce => physics.restitution = Restitution {
+                    coefficient: active.magnitude,
+                    combine_rule: CoefficientCombineRule::Min,
+                },
+                EffectKind::SpeedBoost => (),
+            }
+        }
+        physics
+    }
+
+    fn magnitude(&self, kind: EffectKind) -> Option<f32> {
+        self.active.iter().find(|active| active.kind == kind).map(|active| active.magnitude)
+    }
+}
+
+fn effect_giver_added(
+    mut commands: Commands,
+    query: Query<Entity, Added<EffectGiver>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(ActiveEvents::COLLISION_EVENTS);
+    }
+}
+
+fn effect_giver_collision(
+    mut collisions: EventReader<CollisionEvent>,
+    q_giver: Query<&EffectGiver>,
+    mut q_ball: Query<&mut StatusEffects, With<GolfBall>>,
+) {
+    for collision in collisions.iter() {
+        match collision {
+            CollisionEvent::Stopped(a, b, _) => {
+                let (giver, ball) = if q_ball.contains(*b) { (*a, *b) } else { (*b, *a) };
+                let (Ok(giver), Ok(mut effects)) = (q_giver.get(giver), q_ball.get_mut(ball)) else { continue };
+                for effect in giver.effects.iter() {
+                    effects.grant(effect);
+                }
+            },
+            CollisionEvent::Started(_, _, _) => (),
+        }
+    }
+}
+
+fn tick_status_effects(
+    mut query: Query<&mut StatusEffects>,
+    fixed_time: Res<FixedTime>,
+) {
+    for mut effects in query.iter_mut() {
+        let count = effects.active.len();
+        for active in effects.active.iter_mut() {
+            active.timer.tick(fixed_time.period);
+        }
+        effects.active.retain(|active| !active.timer.finished());
+        if effects.active.len() != count {
+            info!("Effect ended!");
+            effects.dirty = true;
+        }
+    }
+}
+
+// Rebuild the ball's physics from its base values whenever the set of effects changes
+fn apply_status_effects(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut StatusEffects, Option<&GravityScale>, Option<&Damping>, Option<&Restitution>)>,
+) {
+    for (ball, mut effects, gravity_scale, damping, restitution) in query.iter_mut() {
+        if !effects.dirty {
+            continue;
+        }
+        effects.dirty = false;
+
+        let base = *effects.base.get_or_insert(BallPhysics::from_components(gravity_scale, damping, restitution));
+        let physics = effects.physics(base);
+
+        commands.entity(ball).insert((GravityScale(physics.gravity_scale), physics.damping, physics.restitution));
+        if effects.active.is_empty() {
+            effects.base = None;
+        }
+    }
+}
+
+fn speed_boost_thrust(
+    mut commands: Commands,
+    mut query: Query<(Entity, &StatusEffects, &Velocity, Option<&mut ExternalForce>)>,
+) {
+    for (ball, effects, velocity, force) in query.iter_mut() {
+        let thrust = effects.magnitude(EffectKind::SpeedBoost)
+            .map_or(Vec3::ZERO, |magnitude| velocity.linvel.normalize_or_zero() * magnitude);
+        match force {
+            Some(mut force) => if force.force != thrust { force.force = thrust },
+            None if thrust != Vec3::ZERO => { commands.entity(ball).insert(ExternalForce { force: thrust, ..default() }); },
+            None => (),
+        }
+    }
+}
+
+// Out of bounds balls, new shots and freshly loaded levels start without effects
+fn clear_status_effects(
+    mut commands: Commands,
+    mut query: Query<&mut StatusEffects>,
+    q_hud: Query<Entity, With<StatusEffectHud>>,
+) {
+    for mut effects in query.iter_mut() {
+        effects.clear();
+    }
+    for hud in q_hud.iter() {
+        commands.entity(hud).despawn_descendants();
+    }
+}
+
+#[derive(Component)]
+struct StatusEffectHud;
+
+#[derive(Component)]
+struct EffectIcon(EffectKind);
+
+fn setup_status_effect_hud(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..default()
+                },
+                gap: Size::all(Val::Px(10.0)),
+                ..default()
+            },
+            ..default()
+        },
+        StatusEffectHud,
+        Name::new("Status effects"),
+    ));
+}
+
+fn update_status_effect_hud(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    q_effects: Query<&StatusEffects, With<GolfBall>>,
+    q_hud: Query<Entity, With<StatusEffectHud>>,
+    mut q_icons: Query<(&EffectIcon, &mut Text)>,
+) {
+    let Ok(hud) = q_hud.get_single() else { return };
+    let active = q_effects.get_single().map_or(&[][..], |effects| &effects.active[..]);
+
+    let up_to_date = q_icons.iter().count() == active.len()
+        && q_icons.iter().all(|(icon, _)| active.iter().any(|effect| effect.kind == icon.0));
+    if !up_to_date {
+        commands.entity(hud).despawn_descendants();
+        commands.entity(hud).with_children(|parent| {
+            for effect in active.iter() {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    background_color: effect.kind.color().into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: fonts.fira_sans.clone(),
+                                font_size: 24.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ),
+                        EffectIcon(effect.kind),
+                    ));
+                });
+            }
+        });
+        return;
+    }
+
+    for (icon, mut text) in q_icons.iter_mut() {
+        if let Some(effect) = active.iter().find(|effect| effect.kind == icon.0) {
+            let value = format!("{} {:.0}", icon.0.label(), effect.timer.remaining_secs().ceil());
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn low_gravity(duration: f32, magnitude: f32, stacking: EffectStacking) -> StatusEffect {
+        StatusEffect { kind: EffectKind::LowGravity, duration, magnitude, stacking }
+    }
+
+    fn run_for(effects: &mut StatusEffects, secs: f32) {
+        for active in effects.active.iter_mut() {
+            active.timer.tick(Duration::from_secs_f32(secs));
+        }
+    }
+
+    #[test]
+    fn grant_adds_one_entry_per_kind() {
+        let mut effects = StatusEffects::default();
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Refresh));
+        effects.grant(&StatusEffect { kind: EffectKind::Sticky, ..default() });
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Refresh));
+        assert_eq!(effects.active.len(), 2);
+        assert!(effects.dirty);
+    }
+
+    #[test]
+    fn refresh_restarts_with_new_values() {
+        let mut effects = StatusEffects::default();
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Refresh));
+        run_for(&mut effects, 8.0);
+        effects.grant(&low_gravity(5.0, 0.5, EffectStacking::Refresh));
+        assert_eq!(effects.magnitude(EffectKind::LowGravity), Some(0.5));
+        assert_eq!(effects.active[0].timer.remaining_secs(), 5.0);
+    }
+
+    #[test]
+    fn extend_adds_to_the_time_left() {
+        let mut effects = StatusEffects::default();
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Extend));
+        run_for(&mut effects, 8.0);
+        effects.grant(&low_gravity(5.0, 0.2, EffectStacking::Extend));
+        assert_eq!(effects.magnitude(EffectKind::LowGravity), Some(0.2));
+        assert!((effects.active[0].timer.remaining_secs() - 7.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ignore_keeps_the_running_effect() {
+        let mut effects = StatusEffects::default();
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Ignore));
+        run_for(&mut effects, 8.0);
+        effects.dirty = false;
+        effects.grant(&low_gravity(5.0, 0.5, EffectStacking::Ignore));
+        assert_eq!(effects.magnitude(EffectKind::LowGravity), Some(0.1));
+        assert!((effects.active[0].timer.remaining_secs() - 2.0).abs() < 1e-4);
+        assert!(!effects.dirty);
+    }
+
+    #[test]
+    fn clear_only_marks_dirty_when_something_was_active() {
+        let mut effects = StatusEffects::default();
+        effects.clear();
+        assert!(!effects.dirty);
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Refresh));
+        effects.dirty = false;
+        effects.clear();
+        assert!(effects.active.is_empty());
+        assert!(effects.dirty);
+    }
+
+    #[test]
+    fn flight_applies_gravity_and_damping_effects_to_the_ball() {
+        let mut effects = StatusEffects::default();
+        effects.grant(&low_gravity(10.0, 0.1, EffectStacking::Refresh));
+        effects.grant(&StatusEffect { kind: EffectKind::Sticky, magnitude: 0.5, ..default() });
+        let damping = Damping { linear_damping: 0.4, angular_damping: 0.4 };
+        let (gravity_scale, damping) = effects.flight(Some(&GravityScale(2.0)), Some(&damping));
+        assert!((gravity_scale - 0.2).abs() < 1e-6);
+        assert!((damping.linear_damping - 0.9).abs() < 1e-6);
+        assert!((damping.angular_damping - 0.9).abs() < 1e-6);
+    }
+}
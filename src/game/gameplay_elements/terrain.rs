@@ -0,0 +1,146 @@
+use bevy::{prelude::*, scene::SceneInstance};
+use bevy_rapier3d::prelude::*;
+use crate::{game::GameState, in_level};
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<GltfTerrain>()
+            .register_type::<TerrainCollider>()
+            .register_type::<SurfaceType>()
+            .register_type::<MeshSurface>()
+            .register_type::<Vec<MeshSurface>>()
+            .add_system(terrain_added
+                .in_set(OnUpdate(GameState::InProgress))
+                .run_if(in_level)
+            )
+            .add_system(build_terrain_colliders
+                .run_if(in_level)
+            )
+            ;
+    }
+}
+
+/// Static level geometry loaded from a glTF scene
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct GltfTerrain {
+    /// Scene asset path, e.g. `models/canyon.glb#Scene0`
+    pub(crate) path: String,
+    pub(crate) collider: TerrainCollider,
+    /// Surface type per glTF mesh name, unlisted meshes are `SurfaceType::Default`
+    pub(crate) surfaces: Vec<MeshSurface>,
+}
+
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum TerrainCollider {
+    #[default]
+    Trimesh,
+    ConvexDecomposition,
+}
+
+#[derive(Reflect, FromReflect, Clone, PartialEq, Debug, Default)]
+pub(crate) struct MeshSurface {
+    pub(crate) mesh_name: String,
+    pub(crate) surface: SurfaceType,
+}
+
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum SurfaceType {
+    #[default]
+    Default,
+    Grass,
+    Sand,
+    Ice,
+    Rubber,
+}
+
+impl SurfaceType {
+    fn friction(&self) -> Friction {
+        match self {
+            SurfaceType::Default => Friction::default(),
+            SurfaceType::Grass => Friction::new(0.8),
+            SurfaceType::Sand => Friction { coefficient: 2.0, combine_rule: CoefficientCombineRule::Max },
+            SurfaceType::Ice => Friction { coefficient: 0.0, combine_rule: CoefficientCombineRule::Min },
+            SurfaceType::Rubber => Friction::new(1.0),
+        }
+    }
+
+    fn restitution(&self) -> Restitution {
+        match self {
+            SurfaceType::Default | SurfaceType::Grass | SurfaceType::Ice => Restitution::default(),
+            SurfaceType::Sand => Restitution { coefficient: 0.0, combine_rule: CoefficientCombineRule::Min },
+            SurfaceType::Rubber => Restitution { coefficient: 1.0, combine_rule: CoefficientCombineRule::Max },
+        }
+    }
+}
+
+/// Surface tag of a terrain mesh entity
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct Surface(pub(crate) SurfaceType);
+
+/// Terrain whose mesh colliders have been built
+#[derive(Component)]
+struct TerrainReady;
+
+fn terrain_added(
+    mut commands: Commands,
+    query: Query<(Entity, &GltfTerrain, &Transform), Added<GltfTerrain>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, terrain, transform) in query.iter() {
+        info!("Terrain added");
+        commands.entity(entity).insert((
+            asset_server.load::<Scene, _>(terrain.path.as_str()),
+            SpatialBundle {
+                transform: *transform,
+                ..default()
+            },
+            Name::new("Terrain"),
+        ));
+    }
+}
+
+// Wait for the glTF scene to be spawned, then give every mesh in it a static collider
+fn build_terrain_colliders(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    meshes: Res<Assets<Mesh>>,
+    q_terrain: Query<(Entity, &GltfTerrain, &SceneInstance), Without<TerrainReady>>,
+    q_children: Query<&Children>,
+    q_mesh: Query<(&Handle<Mesh>, Option<&Name>)>,
+) {
+    for (entity, terrain, instance) in q_terrain.iter() {
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+
+        let shape = match terrain.collider {
+            TerrainCollider::Trimesh => ComputedColliderShape::TriMesh,
+            TerrainCollider::ConvexDecomposition => ComputedColliderShape::ConvexDecomposition(default()),
+        };
+
+        for mesh_entity in q_children.iter_descendants(entity) {
+            let Ok((handle, name)) = q_mesh.get(mesh_entity) else { continue };
+            let Some(collider) = meshes.get(handle).and_then(|mesh| Collider::from_bevy_mesh(mesh, &shape)) else {
+                warn!("Could not build terrain collider for {:?}", name);
+                continue;
+            };
+
+            let surface = terrain.surfaces.iter()
+                .find(|surface| name.map_or(false, |name| name.as_str() == surface.mesh_name))
+                .map_or(SurfaceType::Default, |surface| surface.surface);
+
+            commands.entity(mesh_entity).insert((
+                collider,
+                RigidBody::Fixed,
+                surface.friction(),
+                surface.restitution(),
+                Surface(surface),
+            ));
+        }
+        commands.entity(entity).insert(TerrainReady);
+    }
+}
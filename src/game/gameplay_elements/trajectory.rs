@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use crate::{AppState, environment::physics::FIXED_TIMESTEP, game::level::LevelSettings};
+use super::{ball::{GolfBall, BallState, BALL_RADIUS, BALL_LINEAR_DAMPING, BALL_ANGULAR_DAMPING}, launcher::{Launcher, LaunchVelocity, ShotCharge, ChargeCurve, MAX_CHARGE_SECS}, spin::{ShotSpin, magnus_acceleration}, status_effect::StatusEffects};
+
+pub struct TrajectoryPlugin;
+
+impl Plugin for TrajectoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(spawn_preview_dots.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(update_trajectory_preview.in_set(OnUpdate(AppState::Playing)))
+            .add_system(cleanup_preview_dots.in_schedule(OnExit(AppState::Playing)))
+            ;
+    }
+}
+
+const PREVIEW_DOTS: usize = 60;
+/// Simulation ticks between two preview dots
+const TICKS_PER_DOT: usize = 3;
+
+#[derive(Component)]
+struct PreviewDot;
+
+/// Predicted ball positions, one per simulation tick, up to the first impact
+///
+/// Mirrors how the ball is integrated: spin curve, then rapier's gravity, damping and position.
+/// `gravity` already includes the ball's gravity scale.
+pub(crate) fn predict_trajectory(
+    rapier_context: &RapierContext,
+    gravity: Vec3,
+    damping: Damping,
+    start: Vec3,
+    velocity: Vec3,
+    angvel: Vec3,
+    duration: f32,
+) -> Vec<Vec3> {
+    let ball = Collider::ball(BALL_RADIUS);
+    // Level geometry only, not the ball itself or sensors like the goal
+    let filter = QueryFilter::exclude_dynamic().exclude_sensors();
+    let dt = FIXED_TIMESTEP;
+    let steps = (duration / dt) as usize;
+
+    let mut points = vec![start];
+    let (mut position, mut velocity, mut angvel) = (start, velocity, angvel);
+    for step in 0..steps {
+        velocity += magnus_acceleration(angvel, velocity) * dt;
+        velocity += gravity * dt;
+        velocity /= 1.0 + dt * damping.linear_damping;
+        angvel /= 1.0 + dt * damping.angular_damping;
+
+        if let Some((_, hit)) = rapier_context.cast_shape(position, Quat::IDENTITY, velocity, &ball, dt, filter) {
+            // Skip whatever the ball already rests on at the launcher
+            if step > 0 || hit.toi > 0.0 {
+                points.push(position + velocity * hit.toi);
+                break;
+            }
+        }
+        position += velocity * dt;
+        points.push(position);
+    }
+    points
+}
+
+fn spawn_preview_dots(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mesh = meshes.add(Mesh::try_from(shape::Icosphere { radius: 0.15, subdivisions: 2 }).unwrap());
+    let material = materials.add(StandardMaterial {
+        emissive: Color::rgb_linear(4.0, 4.0, 4.0),
+        ..default()
+    });
+    for _ in 0..PREVIEW_DOTS {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            PreviewDot,
+            Name::new("Preview dot"),
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_trajectory_preview(
+    rapier_context: Res<RapierContext>,
+    rapier_config: Res<RapierConfiguration>,
+    launch_velocity: Res<LaunchVelocity>,
+    charge_curve: Res<ChargeCurve>,
+    ball_state: Res<State<BallState>>,
+    q_settings: Query<&LevelSettings>,
+    q_launcher: Query<(&Transform, &ShotCharge, &ShotSpin), With<Launcher>>,
+    q_ball: Query<(Option<&GravityScale>, Option<&Damping>, Option<&StatusEffects>), With<GolfBall>>,
+    mut q_dots: Query<(&mut Transform, &mut Visibility), (With<PreviewDot>, Without<Launcher>)>,
+) {
+    let preview_time = q_settings.get_single().map_or(LevelSettings::default().preview_time, |settings| settings.preview_time);
+
+    let mut points = Vec::new();
+    if let (BallState::Aiming, Ok((launcher_trans, charge, spin))) = (&ball_state.0, q_launcher.get_single()) {
+        // Until shoot is held the preview shows a full power shot
+        let power = if charge.is_charging() { charge.power(*charge_curve) } else { MAX_CHARGE_SECS };
+        let velocity = launcher_trans.forward() * launch_velocity.0 * power;
+        let ball_damping = Damping { linear_damping: BALL_LINEAR_DAMPING, angular_damping: BALL_ANGULAR_DAMPING };
+        let (gravity_scale, damping) = match q_ball.get_single() {
+            Ok((gravity_scale, damping, Some(effects))) => effects.flight(gravity_scale, damping),
+            Ok((gravity_scale, damping, None)) => (gravity_scale.map_or(1.0, |scale| scale.0), damping.copied().unwrap_or(ball_damping)),
+            Err(_) => (1.0, ball_damping),
+        };
+        if velocity != Vec3::ZERO && preview_time > 0.0 {
+            points = predict_trajectory(&rapier_context, rapier_config.gravity * gravity_scale, damping, launcher_trans.translation, velocity, spin.angvel(launcher_trans), preview_time);
+        }
+    }
+
+    let mut dot_points = points.iter().skip(TICKS_PER_DOT).step_by(TICKS_PER_DOT);
+    for (mut transform, mut visibility) in q_dots.iter_mut() {
+        match dot_points.next() {
+            Some(point) => {
+                transform.translation = *point;
+                *visibility = Visibility::Visible;
+            },
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+fn cleanup_preview_dots(
+    mut commands: Commands,
+    query: Query<Entity, With<PreviewDot>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
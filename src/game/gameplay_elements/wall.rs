@@ -1,6 +1,6 @@
-use crate::{game::GameState, AppState};
-use super::{create_physical_box, ball::GolfBall};
-use bevy::prelude::*;
+use crate::{game::GameState, in_level, environment::physics::SimulationSet};
+use super::{level_shape::Shape, ball::GolfBall, status_effect::{StatusEffect, StatusEffects, EffectKind, EffectStacking}};
+use bevy::{prelude::*, ecs::system::Command, render::primitives::Aabb, utils::HashMap};
 use bevy_rapier3d::prelude::*;
 
 pub struct WallPlugin;
@@ -11,27 +11,37 @@ impl Plugin for WallPlugin {
             .register_type::<Box>()
             .register_type::<PlainWall>()
             .register_type::<BounceWall>()
+            .register_type::<BounceMode>()
             .register_type::<LowGravWall>()
             .add_system(plain_wall_added
                 .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(AppState::Playing))
+                .run_if(in_level)
+            )
+            .add_system(build_wall_shape
+                .in_set(OnUpdate(GameState::InProgress))
+                .run_if(in_level)
             )
             .add_system(bounce_wall_added
                 .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(AppState::Playing))
+                .run_if(in_level)
+            )
+            .add_system(bounce_wall_collision
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
             )
-            .add_system(bounce_wall_collision)
             .add_system(low_grav_wall_added
                 .in_set(OnUpdate(GameState::InProgress))
-                .in_set(OnUpdate(AppState::Playing))
+                .run_if(in_level)
+            )
+            .add_system(low_grav_wall_collision
+                .in_schedule(CoreSchedule::FixedUpdate)
+                .in_set(SimulationSet::PostPhysics)
             )
-            .add_system(low_grav_wall_collision)
-            .add_system(disable_low_grav)
             ;
     }
 }
 
-#[derive(Component, Reflect)]
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Debug)]
 #[reflect(Component)]
 pub(crate) struct Box {
     pub(crate) x: f32,
@@ -45,50 +55,172 @@ impl Default for Box {
     }
 }
 
+// Walls use their `Shape` if they have one, otherwise a cuboid from `Box`
+fn wall_shape(box_dims: Option<&Box>, shape: Option<&Shape>) -> Shape {
+    shape.copied().unwrap_or_else(|| box_dims.map(Shape::from).unwrap_or_default())
+}
+
+type WallChanged = Or<(Changed<Box>, Changed<Shape>, Added<PlainWall>, Added<BounceWall>, Added<LowGravWall>)>;
+type AnyWall = Or<(With<PlainWall>, With<BounceWall>, With<LowGravWall>)>;
+
+/// Mesh and collider of every wall type, rebuilt when its dimensions or type change
+fn build_wall_shape(
+    query: Query<(Entity, Option<&Box>, Option<&Shape>), (WallChanged, AnyWall)>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, box_dims, shape) in query.iter() {
+        let shape = wall_shape(box_dims, shape);
+        commands.entity(entity)
+            .insert((meshes.add(shape.mesh()), shape.collider()))
+            // Bounds are only calculated for meshes without them
+            .remove::<Aabb>();
+    }
+}
+
+/// What a wall does to the ball, each is its own component
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum WallKind {
+    Plain,
+    Bounce,
+    LowGrav,
+}
+
+impl WallKind {
+    pub(crate) const ALL: [WallKind; 3] = [WallKind::Plain, WallKind::Bounce, WallKind::LowGrav];
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            WallKind::Plain => "Plain",
+            WallKind::Bounce => "Bounce",
+            WallKind::LowGrav => "Gravity",
+        }
+    }
+}
+
+/// A wall's type component with its settings
+#[derive(Clone, Copy)]
+pub(crate) enum WallType {
+    Plain,
+    Bounce(BounceWall),
+    LowGrav(LowGravWall),
+}
+
+impl WallType {
+    pub(crate) fn of(entity: &EntityRef) -> Option<Self> {
+        if entity.contains::<PlainWall>() {
+            Some(WallType::Plain)
+        } else if let Some(bounce) = entity.get::<BounceWall>() {
+            Some(WallType::Bounce(*bounce))
+        } else {
+            entity.get::<LowGravWall>().map(|low_grav| WallType::LowGrav(*low_grav))
+        }
+    }
+
+    pub(crate) fn kind(&self) -> WallKind {
+        match self {
+            WallType::Plain => WallKind::Plain,
+            WallType::Bounce(_) => WallKind::Bounce,
+            WallType::LowGrav(_) => WallKind::LowGrav,
+        }
+    }
+}
+
+/// A type's default settings
+impl From<WallKind> for WallType {
+    fn from(kind: WallKind) -> Self {
+        match kind {
+            WallKind::Plain => WallType::Plain,
+            WallKind::Bounce => WallType::Bounce(BounceWall::default()),
+            WallKind::LowGrav => WallType::LowGrav(LowGravWall::default()),
+        }
+    }
+}
+
+/// Swaps a wall's type in place
+pub(crate) struct SetWallType {
+    pub(crate) entity: Entity,
+    pub(crate) wall: WallType,
+}
+
+impl Command for SetWallType {
+    fn write(self, world: &mut World) {
+        let Some(mut entity) = world.get_entity_mut(self.entity) else { return };
+        entity.remove::<(PlainWall, BounceWall, LowGravWall)>();
+        match self.wall {
+            WallType::Plain => entity.insert(PlainWall),
+            WallType::Bounce(bounce) => entity.insert(bounce),
+            WallType::LowGrav(low_grav) => entity.insert(low_grav),
+        };
+    }
+}
+
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub(crate) struct PlainWall;
 
-// On Box added
+// On Box added, or swapped in for another wall type
 pub(crate) fn plain_wall_added(
-    query: Query<(Entity, &Box, &Transform), Added<PlainWall>>,
+    query: Query<(Entity, &Transform), Added<PlainWall>>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for (entity, box_dims, transform) in query.iter() {
-        let ground_dims = create_physical_box(box_dims.x, box_dims.y, box_dims.z);
+    for (entity, transform) in query.iter() {
         commands.entity(entity).insert((
-            meshes.add(ground_dims.1),
             materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
             SpatialBundle {
                 transform: *transform,
                 ..default()
             },
-            ground_dims.0, // Collider
-            ground_dims.2, // Box
             RigidBody::Fixed,
             // Restitution::new(1.0),
             Name::new("Plain wall"),
-        ));
+        ))
+        .remove::<(Restitution, ActiveEvents)>();
     }
 }
 
-#[derive(Component, Reflect, Default)]
+#[derive(Component, Reflect, Clone, Copy)]
 #[reflect(Component)]
-pub(crate) struct BounceWall;
+pub(crate) struct BounceWall {
+    pub(crate) strength: f32,
+    pub(crate) mode: BounceMode,
+}
+
+impl Default for BounceWall {
+    fn default() -> Self {
+        Self { strength: 100., mode: BounceMode::WorldUp }
+    }
+}
+
+/// Direction of the impulse given to the ball when it leaves a bounce wall
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Debug, Default)]
+pub(crate) enum BounceMode {
+    #[default]
+    WorldUp,
+    SurfaceNormal,
+    LocalAxis(Vec3),
+}
+
+impl BounceMode {
+    /// Impulse direction for a wall rotated by `rotation`, `normal` is the last contact normal from wall to ball
+    fn direction(&self, rotation: Quat, normal: Option<Vec3>) -> Vec3 {
+        match self {
+            BounceMode::WorldUp => Vec3::Y,
+            BounceMode::SurfaceNormal => normal.unwrap_or(Vec3::Y),
+            BounceMode::LocalAxis(axis) => (rotation * *axis).normalize_or_zero(),
+        }
+    }
+}
 
 pub(crate) fn bounce_wall_added(
-    query: Query<(Entity, &Box, &Transform), Added<BounceWall>>,
+    query: Query<(Entity, &Transform), Added<BounceWall>>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for (entity, box_dims, transform) in query.iter() {
+    for (entity, transform) in query.iter() {
         info!("Bounce added");
-        let ground_dims = create_physical_box(box_dims.x, box_dims.y, box_dims.z);
         commands.entity(entity).insert((
-            meshes.add(ground_dims.1),
             materials.add(StandardMaterial {
                 emissive: Color::rgb_linear(6.99, 6.99, 1.0),
                 ..default()
@@ -97,8 +229,6 @@ pub(crate) fn bounce_wall_added(
                 transform: *transform,
                 ..default()
             },
-            ground_dims.0, // Collider
-            ground_dims.2, // Box
             RigidBody::Fixed,
             Restitution::new(1.0),
             ActiveEvents::COLLISION_EVENTS,
@@ -110,40 +240,78 @@ pub(crate) fn bounce_wall_added(
 fn bounce_wall_collision(
     mut commands: Commands,
     mut collisions: EventReader<CollisionEvent>,
-    q_wall: Query<Entity, With<BounceWall>>,
+    rapier_context: Res<RapierContext>,
+    q_wall: Query<(&BounceWall, &GlobalTransform)>,
     q_ball: Query<Entity, With<GolfBall>>,
+    mut last_normals: Local<HashMap<(Entity, Entity), Vec3>>,
 ) {
+    // Remember the latest contact normal (pointing from wall to ball) while touching,
+    // the manifold is usually empty once the contact has stopped
+    for ball in q_ball.iter() {
+        for pair in rapier_context.contacts_with(ball) {
+            let (wall, flip) = if pair.collider1() == ball { (pair.collider2(), -1.) } else { (pair.collider1(), 1.) };
+            if !pair.has_any_active_contacts() || q_wall.get(wall).is_err() {
+                continue;
+            }
+            if let Some(manifold) = pair.manifold(0) {
+                last_normals.insert((wall, ball), manifold.normal() * flip);
+            }
+        }
+    }
+
     for collision in collisions.iter() {
-        let bounce_strength = 100.;
-        
         match collision {
             CollisionEvent::Stopped(a, b, _) => {
-                if q_wall.get(*a).is_ok() || q_wall.get(*b).is_ok() {
-                    info!("Bounce!");
-                    let ball = q_ball.single();
-                    commands.entity(ball).insert(ExternalImpulse {impulse: Vec3::Y * bounce_strength, ..default() });
+                let (wall, ball) = if q_ball.contains(*b) { (*a, *b) } else { (*b, *a) };
+                let Ok((bounce_wall, wall_transform)) = q_wall.get(wall) else { continue };
+                if !q_ball.contains(ball) {
+                    continue;
                 }
+                info!("Bounce!");
+
+                let normal = last_normals.remove(&(wall, ball));
+                let (_, rotation, _) = wall_transform.to_scale_rotation_translation();
+                let direction = bounce_wall.mode.direction(rotation, normal);
+                commands.entity(ball).insert(ExternalImpulse {impulse: direction * bounce_wall.strength, ..default() });
             },
             CollisionEvent::Started(_, _, _) => (),
         }
     }
 }
 
-#[derive(Component, Reflect, Default)]
+#[derive(Component, Reflect, Clone, Copy)]
 #[reflect(Component)]
-pub(crate) struct LowGravWall;
+pub(crate) struct LowGravWall {
+    pub(crate) gravity_scale: f32,
+    pub(crate) duration: f32,
+    pub(crate) stacking: EffectStacking,
+}
+
+impl Default for LowGravWall {
+    fn default() -> Self {
+        Self { gravity_scale: 0.1, duration: 10.0, stacking: EffectStacking::Refresh }
+    }
+}
+
+impl LowGravWall {
+    fn effect(&self) -> StatusEffect {
+        StatusEffect {
+            kind: EffectKind::LowGravity,
+            duration: self.duration,
+            magnitude: self.gravity_scale,
+            stacking: self.stacking,
+        }
+    }
+}
 
 pub(crate) fn low_grav_wall_added(
-    query: Query<(Entity, &Box, &Transform), Added<LowGravWall>>,
+    query: Query<(Entity, &Transform), Added<LowGravWall>>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for (entity, box_dims, transform) in query.iter() {
+    for (entity, transform) in query.iter() {
         info!("LowGrav added");
-        let ground_dims = create_physical_box(box_dims.x, box_dims.y, box_dims.z);
         commands.entity(entity).insert((
-            meshes.add(ground_dims.1),
             materials.add(StandardMaterial {
                 emissive: Color::rgb_linear(1.0, 1.0, 6.99),
                 ..default()
@@ -152,8 +320,6 @@ pub(crate) fn low_grav_wall_added(
                 transform: *transform,
                 ..default()
             },
-            ground_dims.0, // Collider
-            ground_dims.2, // Box
             RigidBody::Fixed,
             Restitution::new(1.0),
             ActiveEvents::COLLISION_EVENTS,
@@ -162,43 +328,45 @@ pub(crate) fn low_grav_wall_added(
     }
 }
 
-#[derive(Component)]
-struct GravityEffectTimer(Timer);
-
 fn low_grav_wall_collision(
-    mut commands: Commands,
     mut collisions: EventReader<CollisionEvent>,
-    q_wall: Query<Entity, With<LowGravWall>>,
-    q_ball: Query<Entity, With<GolfBall>>,
+    q_wall: Query<&LowGravWall>,
+    mut q_ball: Query<&mut StatusEffects, With<GolfBall>>,
 ) {
     for collision in collisions.iter() {
-        let grav_scale = 0.1;
-        let low_grav_time = 10.0;
-        
         match collision {
             CollisionEvent::Stopped(a, b, _) => {
-                if q_wall.get(*a).is_ok() || q_wall.get(*b).is_ok() {
-                    info!("LowGrav!");
-                    let ball = q_ball.single();
-                    commands.entity(ball).insert(GravityScale(grav_scale));
-                    commands.entity(ball).insert(GravityEffectTimer(Timer::from_seconds(low_grav_time, TimerMode::Once)));
-                }
+                let (wall, ball) = if q_ball.contains(*b) { (*a, *b) } else { (*b, *a) };
+                let (Ok(low_grav_wall), Ok(mut effects)) = (q_wall.get(wall), q_ball.get_mut(ball)) else { continue };
+                info!("LowGrav!");
+                effects.grant(&low_grav_wall.effect());
             },
             CollisionEvent::Started(_, _, _) => (),
         }
     }
 }
 
-fn disable_low_grav(
-    mut commands: Commands,
-    mut q_ball: Query<(Entity, &mut GravityEffectTimer), With<GolfBall>>,
-    time: Res<Time>,
-) {
-    for (ball, mut timer) in q_ball.iter_mut() {
-        if timer.0.tick(time.delta()).just_finished() {
-            commands.entity(ball).insert(GravityScale(1.0));
-            commands.entity(ball).remove::<GravityEffectTimer>();
-            info!("Removed low grav!")
-        }
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use super::*;
+
+    #[test]
+    fn world_up_ignores_wall_and_contact() {
+        let direction = BounceMode::WorldUp.direction(Quat::from_rotation_z(FRAC_PI_2), Some(Vec3::X));
+        assert_eq!(direction, Vec3::Y);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn surface_normal_uses_contact_or_falls_back_to_up() {
+        assert_eq!(BounceMode::SurfaceNormal.direction(Quat::IDENTITY, Some(Vec3::X)), Vec3::X);
+        assert_eq!(BounceMode::SurfaceNormal.direction(Quat::IDENTITY, None), Vec3::Y);
+    }
+
+    #[test]
+    fn local_axis_turns_with_the_wall_and_is_normalized() {
+        let direction = BounceMode::LocalAxis(Vec3::Y * 3.0).direction(Quat::from_rotation_z(-FRAC_PI_2), None);
+        assert!(direction.abs_diff_eq(Vec3::X, 1e-5));
+        assert_eq!(BounceMode::LocalAxis(Vec3::ZERO).direction(Quat::IDENTITY, None), Vec3::ZERO);
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use crate::AppState;
+use crate::camera::{CameraTransition, MainCamera, OrbitCamera, blend_camera_transition};
+use crate::game::game_manager::CurrentLevel;
+use crate::game::gameplay_elements::{goal::Goal, launcher::{Launcher, yaw_towards}};
+use crate::loading::FontAssets;
+use super::LevelSettings;
+
+pub struct LevelIntroPlugin;
+
+impl Plugin for LevelIntroPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<IntroPath>()
+            .register_type::<Vec<Vec3>>()
+            .add_system(start_intro
+                .in_set(OnUpdate(AppState::Playing))
+            )
+            .add_system(play_intro
+                .in_set(OnUpdate(AppState::Playing))
+                .after(start_intro)
+                .after(blend_camera_transition)
+            )
+            ;
+    }
+}
+
+/// Authored camera path for the level intro, saved as an entity of its own in the level file
+///
+/// Levels without one fly from the goal to the launcher.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct IntroPath {
+    /// Camera positions from the goal end to the launcher end, at least two
+    pub points: Vec<Vec3>,
+    /// Seconds to fly the whole path
+    pub duration: f32,
+}
+
+impl Default for IntroPath {
+    fn default() -> Self {
+        Self { points: Vec::new(), duration: INTRO_SECS }
+    }
+}
+
+const INTRO_SECS: f32 = 4.0;
+
+/// Camera flying the intro path, any button skips it
+#[derive(Component)]
+struct LevelIntro {
+    points: Vec<Vec3>,
+    goal: Vec3,
+    launcher: Vec3,
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct IntroTitle;
+
+/// Run condition, gameplay input is ignored during the intro
+pub fn intro_playing(query: Query<(), With<LevelIntro>>) -> bool {
+    !query.is_empty()
+}
+
+// Beyond the goal looking back, high over the middle, then down behind the launcher
+fn automatic_path(goal: Vec3, launcher: Vec3) -> Vec<Vec3> {
+    let span = Vec3::new(launcher.x - goal.x, 0.0, launcher.z - goal.z);
+    let direction = span.try_normalize().unwrap_or(Vec3::Z);
+    vec![
+        goal - direction * 10.0 + Vec3::Y * 5.0,
+        (goal + launcher) / 2.0 + Vec3::Y * (span.length() * 0.4 + 8.0),
+        launcher + direction * 10.0 + Vec3::Y * 4.0,
+    ]
+}
+
+/// Catmull-Rom spline through `points`, `t` from 0 at the first point to 1 at the last
+fn catmull_rom(points: &[Vec3], t: f32) -> Vec3 {
+    let segments = points.len() - 1;
+    let scaled = t.clamp(0.0, 1.0) * segments as f32;
+    let i = (scaled.floor() as usize).min(segments - 1);
+    let t = scaled - i as f32;
+    // The end points are repeated to get tangents at the ends
+    let point = |j: usize| points[j.min(segments)];
+    let (p0, p1, p2, p3) = (point(i.saturating_sub(1)), point(i), point(i + 1), point(i + 2));
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+}
+
+// Goals are only spawned with a new level, unlike the launcher which respawns after every shot
+fn start_intro(
+    mut commands: Commands,
+    q_goal: Query<&Transform, Added<Goal>>,
+    q_launcher: Query<&Transform, With<Launcher>>,
+    q_path: Query<&IntroPath>,
+    q_settings: Query<&LevelSettings>,
+    q_camera: Query<Entity, With<MainCamera>>,
+    q_title: Query<Entity, With<IntroTitle>>,
+    current_level: Res<CurrentLevel>,
+    fonts: Res<FontAssets>,
+) {
+    let (Some(goal), Some(launcher), Ok(camera)) = (q_goal.iter().next(), q_launcher.iter().next(), q_camera.get_single()) else { return };
+    let (goal, launcher) = (goal.translation, launcher.translation);
+
+    let (points, duration) = match q_path.get_single() {
+        Ok(path) if path.points.len() >= 2 => (path.points.clone(), path.duration),
+        _ => (automatic_path(goal, launcher), INTRO_SECS),
+    };
+    info!("Level intro over {} points", points.len());
+    commands.entity(camera)
+        .insert(LevelIntro {
+            points,
+            goal,
+            launcher,
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+        })
+        .remove::<CameraTransition>();
+
+    let settings = q_settings.get_single().cloned().unwrap_or_default();
+    let name = if settings.name.is_empty() { format!("Level {}", current_level.index) } else { settings.name };
+    let text_style = TextStyle {
+        font: fonts.fira_sans.clone(),
+        font_size: 60.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    for title in q_title.iter() {
+        commands.entity(title).despawn_recursive();
+    }
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(60.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Undefined),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        },
+        IntroTitle,
+        Name::new("Level intro"),
+    ))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section(name, text_style.clone()));
+        parent.spawn(TextBundle::from_section(
+            format!("Par {}", settings.par),
+            TextStyle { font_size: 36.0, ..text_style },
+        ));
+    });
+}
+
+// Skipped on release so the press can't start charging a shot
+fn play_intro(
+    mut commands: Commands,
+    mut q_camera: Query<(Entity, &mut Transform, &mut LevelIntro, Option<&mut OrbitCamera>), With<MainCamera>>,
+    q_title: Query<Entity, With<IntroTitle>>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    touches: Res<Touches>,
+    time: Res<Time>,
+) {
+    let Ok((camera, mut trans, mut intro, orbit)) = q_camera.get_single_mut() else { return };
+
+    intro.timer.tick(time.delta());
+    let t = intro.timer.percent();
+    let t = t * t * (3.0 - 2.0 * t);
+    let target = intro.goal.lerp(intro.launcher, t);
+    *trans = Transform::from_translation(catmull_rom(&intro.points, t)).looking_at(target, Vec3::Y);
+
+    let skipped = keys.get_just_released().next().is_some()
+        || mouse.get_just_released().next().is_some()
+        || gamepad_buttons.get_just_released().next().is_some()
+        || touches.iter_just_released().next().is_some();
+    if !intro.timer.finished() && !skipped {
+        return;
+    }
+
+    // Hand over to the follow camera looking down the level
+    if let Some(mut orbit) = orbit {
+        orbit.yaw = yaw_towards(intro.launcher, intro.goal);
+    }
+    commands.entity(camera)
+        .remove::<LevelIntro>()
+        .insert(CameraTransition::new(*trans));
+    for title in q_title.iter() {
+        commands.entity(title).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spline_passes_through_every_point() {
+        let points = [Vec3::ZERO, Vec3::new(4.0, 2.0, 0.0), Vec3::new(6.0, 0.0, -3.0), Vec3::new(2.0, 5.0, 1.0)];
+        for (i, point) in points.iter().enumerate() {
+            let t = i as f32 / (points.len() - 1) as f32;
+            assert!(catmull_rom(&points, t).abs_diff_eq(*point, 1e-5), "point {i}");
+        }
+    }
+
+    #[test]
+    fn spline_clamps_outside_the_path() {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 0.0, 1.0)];
+        assert_eq!(catmull_rom(&points, -1.0), points[0]);
+        assert!(catmull_rom(&points, 2.0).abs_diff_eq(points[2], 1e-5));
+    }
+
+    #[test]
+    fn evenly_spaced_line_is_followed_at_constant_speed_between_the_ends() {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::X * 2.0, Vec3::X * 3.0];
+        assert!(catmull_rom(&points, 0.5).abs_diff_eq(Vec3::X * 1.5, 1e-5));
+        assert!(catmull_rom(&points, 1.25 / 3.0).abs_diff_eq(Vec3::X * 1.25, 1e-5));
+    }
+
+    #[test]
+    fn two_points_make_a_single_segment() {
+        let points = [Vec3::ZERO, Vec3::Y * 2.0];
+        assert!(catmull_rom(&points, 0.5).abs_diff_eq(Vec3::Y, 1e-5));
+    }
+}
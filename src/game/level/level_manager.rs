@@ -1,7 +1,12 @@
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
 use std::{fs::File, io::Write};
-use bevy::{prelude::*, tasks::IoTaskPool};
-use crate::game::{game_manager::GameState, gameplay_elements::{goal::Goal, launcher::Launcher, wall}};
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::tasks::IoTaskPool;
+use crate::game::{game_manager::GameState, gameplay_elements::{goal::Goal, launcher::Launcher, wall, status_effect, level_shape, terrain}};
 use crate::game::gameplay_elements::ball::GolfBall;
+use super::{LevelSettings, IntroPath};
 
 pub struct LevelManagerPlugin;
 
@@ -10,6 +15,7 @@ impl Plugin for LevelManagerPlugin {
         app
             .add_event::<SaveLevelEvent>()
             .add_event::<LoadLevelEvent>()
+            .init_resource::<LevelFileStatus>()
             .add_system(load_level_system)
             .add_system(save_scene_system.run_if(on_event::<SaveLevelEvent>()))
             // .add_system(load_scene_system.in_schedule(OnEnter(GameState::InProgress)))
@@ -18,11 +24,85 @@ impl Plugin for LevelManagerPlugin {
     }
 }
 
-pub struct SaveLevelEvent{
-    pub name: String,
+pub struct SaveLevelEvent {
+    pub level: LevelFile,
 }
 pub struct LoadLevelEvent {
-    pub level: usize,
+    pub level: LevelFile,
+}
+
+/// Folder level files are saved to and listed from
+pub const LEVELS_DIR: &str = "assets/levels";
+pub const LEVEL_EXTENSION: &str = ".scn.ron";
+
+/// A level file, numbered levels are saved as `level_{index}`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LevelFile {
+    Index(usize),
+    Name(String),
+}
+
+impl LevelFile {
+    /// Numbers are level indices, anything else a file name with or without the extension
+    pub fn parse(input: &str) -> Self {
+        let input = input.trim();
+        match input.parse() {
+            Ok(index) => LevelFile::Index(index),
+            Err(_) => LevelFile::Name(input.trim_end_matches(LEVEL_EXTENSION).to_string()),
+        }
+    }
+
+    /// File name without the extension
+    pub fn stem(&self) -> String {
+        match self {
+            LevelFile::Index(index) => format!("level_{index}"),
+            LevelFile::Name(name) => name.clone(),
+        }
+    }
+
+    /// Path on disk, for file management in the dev UI
+    pub fn file_path(&self) -> String {
+        format!("{LEVELS_DIR}/{}{LEVEL_EXTENSION}", self.stem())
+    }
+
+    fn asset_path(&self) -> String {
+        format!("levels/{}{LEVEL_EXTENSION}", self.stem())
+    }
+}
+
+/// Outcome of the last level file operation, shown in the dev UI
+///
+/// Shared with the tasks that write level files.
+#[derive(Resource, Clone, Default)]
+pub struct LevelFileStatus(Arc<Mutex<String>>);
+
+impl LevelFileStatus {
+    pub fn set(&self, message: impl Into<String>) {
+        if let Ok(mut status) = self.0.lock() {
+            *status = message.into();
+        }
+    }
+
+    pub fn get(&self) -> String {
+        self.0.lock().map_or_else(|_| String::new(), |status| status.clone())
+    }
+}
+
+/// Level files in `LEVELS_DIR` without their extension, none on the web
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn level_files() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(LEVELS_DIR) else { return Vec::new() };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| name.strip_suffix(LEVEL_EXTENSION).map(str::to_string))
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn level_files() -> Vec<String> {
+    Vec::new()
 }
 
 // clean up level
@@ -40,18 +120,32 @@ fn clean_up_level(
 #[reflect(Component)]
 pub struct Level;
 
-// load level
+// load level, replacing the current one
 fn load_level_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut scenes: ResMut<Assets<DynamicScene>>,
     mut events: EventReader<LoadLevelEvent>,
+    query: Query<Entity, With<Level>>,
 ) {
     for event in events.iter() {
-        info!("Loading level!");
+        info!("Loading level {}", event.level.stem());
+        for level in query.iter() {
+            commands.entity(level).despawn_recursive();
+        }
+        // The file may have been saved over since it was last loaded. `reload_asset` on its own
+        // would spawn the stale scene straight away, then write the fresh one into the despawned
+        // level's entities when it's modified. Dropping the stale copy first makes the new level
+        // wait until the file is read again.
+        let path = event.level.asset_path();
+        let scene = asset_server.load(path.as_str());
+        if scenes.remove(&scene).is_some() {
+            asset_server.reload_asset(path.as_str());
+        }
         commands.spawn((
             Level,
             DynamicSceneBundle {
-                scene: asset_server.load(format!("levels/level_{}.scn.ron", event.level)),
+                scene,
                 visibility: Visibility::Visible,
                 ..default()
             },
@@ -60,29 +154,33 @@ fn load_level_system(
     }
 }
 
-fn save_scene_system(
-    world: &mut World,
-) {
-    info!("Saving!");
-
-    let events = world.resource::<Events<SaveLevelEvent>>();
-    let mut event_reader = events.get_reader();
-    // let filename = event_reader.iter(SaveLevelEvent).last();
-
-    let name = event_reader.iter(events).last().unwrap().name.clone();
-
-    info!("Name {}", name);
-
+/// Types written to level files, everything else on level entities is rebuilt when they're added
+pub(crate) fn level_type_registry() -> AppTypeRegistry {
     let custom_type_registry = AppTypeRegistry::default();
 
     // let mut custom_registry = scene_world.resource_mut::<AppTypeRegistry>();
     custom_type_registry.write().register::<GolfBall>();
     custom_type_registry.write().register::<Launcher>();
     custom_type_registry.write().register::<Goal>();
+    custom_type_registry.write().register::<LevelSettings>();
+    custom_type_registry.write().register::<IntroPath>();
+    custom_type_registry.write().register::<Vec<Vec3>>();
     custom_type_registry.write().register::<wall::Box>();
+    custom_type_registry.write().register::<level_shape::Shape>();
+    custom_type_registry.write().register::<terrain::GltfTerrain>();
+    custom_type_registry.write().register::<terrain::TerrainCollider>();
+    custom_type_registry.write().register::<terrain::MeshSurface>();
+    custom_type_registry.write().register::<Vec<terrain::MeshSurface>>();
+    custom_type_registry.write().register::<terrain::SurfaceType>();
     custom_type_registry.write().register::<wall::PlainWall>();
     custom_type_registry.write().register::<wall::BounceWall>();
+    custom_type_registry.write().register::<wall::BounceMode>();
     custom_type_registry.write().register::<wall::LowGravWall>();
+    custom_type_registry.write().register::<status_effect::EffectStacking>();
+    custom_type_registry.write().register::<status_effect::EffectKind>();
+    custom_type_registry.write().register::<status_effect::StatusEffect>();
+    custom_type_registry.write().register::<Vec<status_effect::StatusEffect>>();
+    custom_type_registry.write().register::<status_effect::EffectGiver>();
     
     // custom_type_registry.write().register::<GlobalTransform>();
     // custom_type_registry.write().register::<Affine3A>();
@@ -93,6 +191,24 @@ fn save_scene_system(
     custom_type_registry.write().register::<Vec3>();
     custom_type_registry.write().register::<Quat>();
 
+    custom_type_registry
+}
+
+fn save_scene_system(
+    world: &mut World,
+) {
+    info!("Saving!");
+
+    let events = world.resource::<Events<SaveLevelEvent>>();
+    let mut event_reader = events.get_reader();
+    // let filename = event_reader.iter(SaveLevelEvent).last();
+
+    let level = event_reader.iter(events).last().unwrap().level.clone();
+
+    info!("Name {}", level.stem());
+
+    let custom_type_registry = level_type_registry();
+
     info!("After builder");
 
     let mut query = world.query_filtered::<(Entity, &Children), With<Level>>();
@@ -117,23 +233,65 @@ fn save_scene_system(
     let scene = builder.build();
     info!("Scene built");
 
+    let status = world.resource::<LevelFileStatus>().clone();
     // Scenes can be serialized like this:
     // let serialized_scene = scene.serialize_ron(&type_registry).unwrap();
-    let serialized_scene = scene.serialize_ron(&custom_type_registry).unwrap();
+    let serialized_scene = match scene.serialize_ron(&custom_type_registry) {
+        Ok(serialized_scene) => serialized_scene,
+        Err(error) => {
+            error!("Couldn't serialize {}: {}", level.stem(), error);
+            status.set(format!("Saving {} failed: {}", level.stem(), error));
+            return;
+        }
+    };
 
     // Showing the scene in the console
     info!("{}", serialized_scene);
 
     // Writing the scene to a new file. Using a task to avoid calling the filesystem APIs in a system
     // as they are blocking
-    // This can't work in WASM as there is no filesystem access
     #[cfg(not(target_arch = "wasm32"))]
     IoTaskPool::get()
         .spawn(async move {
-            // Write the scene RON data to file
-            File::create(format!("assets/levels/{name}.scn.ron"))
-                .and_then(|mut file| file.write(serialized_scene.as_bytes()))
-                .expect("Error while writing scene to file");
+            let path = level.file_path();
+            match File::create(&path).and_then(|mut file| file.write_all(serialized_scene.as_bytes())) {
+                Ok(()) => status.set(format!("Saved {}", level.stem())),
+                Err(error) => {
+                    error!("Couldn't write {}: {}", path, error);
+                    status.set(format!("Saving {} failed: {}", level.stem(), error));
+                }
+            }
         })
         .detach();
+
+    // There is no filesystem access on the web
+    #[cfg(target_arch = "wasm32")]
+    status.set(format!("Can't save {} on the web", level.stem()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_indices_and_anything_else_a_name() {
+        assert_eq!(LevelFile::parse("3"), LevelFile::Index(3));
+        assert_eq!(LevelFile::parse(" 12 "), LevelFile::Index(12));
+        assert_eq!(LevelFile::parse("level_3"), LevelFile::Name("level_3".to_string()));
+        assert_eq!(LevelFile::parse("-1"), LevelFile::Name("-1".to_string()));
+    }
+
+    #[test]
+    fn extension_is_optional() {
+        assert_eq!(LevelFile::parse("windmill.scn.ron"), LevelFile::Name("windmill".to_string()));
+        assert_eq!(LevelFile::parse("windmill"), LevelFile::Name("windmill".to_string()));
+    }
+
+    #[test]
+    fn indices_and_names_map_to_files() {
+        assert_eq!(LevelFile::Index(2).file_path(), "assets/levels/level_2.scn.ron");
+        assert_eq!(LevelFile::Name("windmill".to_string()).file_path(), "assets/levels/windmill.scn.ron");
+        assert_eq!(LevelFile::Index(2).asset_path(), "levels/level_2.scn.ron");
+        assert_eq!(LevelFile::parse("level_2").file_path(), LevelFile::Index(2).file_path());
+    }
 }
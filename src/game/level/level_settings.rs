@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+
+pub struct LevelSettingsPlugin;
+
+impl Plugin for LevelSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LevelSettings>();
+    }
+}
+
+/// Per level settings, saved as an entity of its own in the level file
+///
+/// Levels without one use the defaults.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct LevelSettings {
+    /// Shown by the level intro, levels without a name show their number
+    pub name: String,
+    /// Expected number of shots
+    pub par: u32,
+    /// Seconds of ball flight shown by the aiming preview, 0 disables it
+    pub preview_time: f32,
+    /// Launcher pitch limits in degrees, positive aims up
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    /// Degrees the launcher may turn either way from where it started, 180 or more is unlimited
+    pub yaw_range: f32,
+}
+
+impl Default for LevelSettings {
+    fn default() -> Self {
+        Self { name: String::new(), par: 3, preview_time: 2.0, min_pitch: -10.0, max_pitch: 80.0, yaw_range: 180.0 }
+    }
+}
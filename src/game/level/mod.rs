@@ -1,14 +1,20 @@
 pub(crate) mod level_manager;
+pub(crate) mod level_settings;
+pub(crate) mod level_intro;
 
 use bevy::prelude::*;
 
-use self::level_manager::LevelManagerPlugin;
+use self::{level_manager::LevelManagerPlugin, level_settings::LevelSettingsPlugin, level_intro::LevelIntroPlugin};
 pub use level_manager::Level;
+pub use level_settings::LevelSettings;
+pub use level_intro::{IntroPath, intro_playing};
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(LevelManagerPlugin);
+        app.add_plugin(LevelManagerPlugin)
+            .add_plugin(LevelSettingsPlugin)
+            .add_plugin(LevelIntroPlugin);
     }
 }
\ No newline at end of file
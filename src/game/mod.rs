@@ -6,6 +6,7 @@ mod level_test_env;
 use bevy::prelude::*;
 use self::{game_manager::GameManagerPlugin, level::LevelPlugin, gameplay_elements::GameplayElementsPlugin};
 pub use self::game_manager::GameState;
+pub(crate) use self::game_manager::CurrentLevel;
 
 pub struct GamePlugin;
 
@@ -7,14 +7,25 @@ mod environment;
 mod game;
 mod tools;
 mod camera;
+mod settings;
+mod minimap;
+mod photo;
+mod editor;
 
 use actions::ActionsPlugin;
 use audio::InternalAudioPlugin;
 use camera::InternalCameraPlugin;
 use loading::LoadingPlugin;
 use menu::MenuPlugin;
+use settings::SettingsPlugin;
+use minimap::MinimapPlugin;
+use photo::PhotoPlugin;
+use editor::EditorPlugin;
 use environment::EnvironmentPlugin;
 use game::GamePlugin;
+pub use environment::physics::{rapier_configuration, FIXED_TIMESTEP, PHYSICS_SUBSTEPS};
+pub use game::gameplay_elements::ball::{BallPhysicsBundle, BALL_RADIUS};
+pub use game::gameplay_elements::launcher::{MAX_LAUNCH_VELOCITY, MAX_CHARGE_SECS};
 // use player::PlayerPlugin;
 // use tools::ToolsPlugin;
 
@@ -29,6 +40,13 @@ enum AppState {
     Loading,
     Playing,
     Menu,
+    Settings,
+    Editor,
+}
+
+/// Run condition for level element setup, the level exists while playing and while editing
+pub(crate) fn in_level(state: Res<State<AppState>>) -> bool {
+    matches!(state.0, AppState::Playing | AppState::Editor)
 }
 
 pub struct AppPlugin;
@@ -38,9 +56,13 @@ impl Plugin for AppPlugin {
         app.add_state::<AppState>()
             .add_plugin(LoadingPlugin)
             .add_plugin(MenuPlugin)
+            .add_plugin(SettingsPlugin)
             .add_plugin(InternalAudioPlugin)
             .add_plugin(ActionsPlugin)
             .add_plugin(InternalCameraPlugin)
+            .add_plugin(MinimapPlugin)
+            .add_plugin(PhotoPlugin)
+            .add_plugin(EditorPlugin)
             .add_plugin(EnvironmentPlugin)
             .add_plugin(GamePlugin)
             
@@ -1,4 +1,5 @@
 #![allow(clippy::too_many_arguments, clippy::type_complexity)]
+use crate::game::gameplay_elements::launcher::{ChargeCurve, ControlScheme};
 use crate::loading::FontAssets;
 use crate::AppState;
 use bevy::prelude::*;
@@ -10,6 +11,10 @@ impl Plugin for MenuPlugin {
         app.init_resource::<ButtonColors>()
             .add_system(setup_menu.in_schedule(OnEnter(AppState::Menu)))
             .add_system(click_play_button.in_set(OnUpdate(AppState::Menu)))
+            .add_system(click_controls_button.in_set(OnUpdate(AppState::Menu)))
+            .add_system(click_charge_curve_button.in_set(OnUpdate(AppState::Menu)))
+            .add_system(click_bindings_button.in_set(OnUpdate(AppState::Menu)))
+            .add_system(click_editor_button.in_set(OnUpdate(AppState::Menu)))
             .add_system(cleanup_menu.in_schedule(OnExit(AppState::Menu)));
     }
 }
@@ -32,42 +37,114 @@ impl Default for ButtonColors {
 #[derive(Component)]
 struct MenuCamera;
 
+#[derive(Component)]
+struct MenuRoot;
+
+#[derive(Component)]
+struct PlayButton;
+
+#[derive(Component)]
+struct ControlsButton;
+
+#[derive(Component)]
+struct ChargeCurveButton;
+
+#[derive(Component)]
+struct BindingsButton;
+
+#[derive(Component)]
+struct EditorButton;
+
 fn setup_menu(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
     button_colors: Res<ButtonColors>,
+    control_scheme: Res<ControlScheme>,
+    charge_curve: Res<ChargeCurve>,
 ) {
     commands.spawn((Camera2dBundle::default(), MenuCamera));
+    let text_style = TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size: 40.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
     commands
-        .spawn(ButtonBundle {
-            style: Style {
-                size: Size::new(Val::Px(120.0), Val::Px(50.0)),
-                margin: UiRect::all(Val::Auto),
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::all(Val::Px(20.0)),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
-            background_color: button_colors.normal.into(),
-            ..Default::default()
-        })
+            MenuRoot,
+        ))
         .with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Play",
-                TextStyle {
-                    font: font_assets.fira_sans.clone(),
-                    font_size: 40.0,
-                    color: Color::rgb(0.9, 0.9, 0.9),
-                },
-            ));
+            parent
+                .spawn((menu_button(&button_colors, 120.0), PlayButton))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Play", text_style.clone()));
+                });
+            parent
+                .spawn((menu_button(&button_colors, 360.0), ControlsButton))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        controls_label(*control_scheme),
+                        text_style.clone(),
+                    ));
+                });
+            parent
+                .spawn((menu_button(&button_colors, 360.0), ChargeCurveButton))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        charge_curve_label(*charge_curve),
+                        text_style.clone(),
+                    ));
+                });
+            parent
+                .spawn((menu_button(&button_colors, 360.0), BindingsButton))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Key bindings", text_style.clone()));
+                });
+            parent
+                .spawn((menu_button(&button_colors, 360.0), EditorButton))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Level editor", text_style));
+                });
         });
 }
 
+fn menu_button(button_colors: &ButtonColors, width: f32) -> ButtonBundle {
+    ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Px(width), Val::Px(50.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: button_colors.normal.into(),
+        ..Default::default()
+    }
+}
+
+fn controls_label(control_scheme: ControlScheme) -> String {
+    format!("Controls: {}", control_scheme.label())
+}
+
+fn charge_curve_label(charge_curve: ChargeCurve) -> String {
+    format!("Power: {}", charge_curve.label())
+}
+
 fn click_play_button(
     button_colors: Res<ButtonColors>,
     mut state: ResMut<NextState<AppState>>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (Changed<Interaction>, With<PlayButton>),
     >,
 ) {
     for (interaction, mut color) in &mut interaction_query {
@@ -85,11 +162,115 @@ fn click_play_button(
     }
 }
 
+fn click_controls_button(
+    button_colors: Res<ButtonColors>,
+    mut control_scheme: ResMut<ControlScheme>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<ControlsButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, mut color, children) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *control_scheme = control_scheme.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = controls_label(*control_scheme);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+fn click_charge_curve_button(
+    button_colors: Res<ButtonColors>,
+    mut charge_curve: ResMut<ChargeCurve>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<ChargeCurveButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, mut color, children) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *charge_curve = charge_curve.next();
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = charge_curve_label(*charge_curve);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+fn click_bindings_button(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<NextState<AppState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<BindingsButton>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                state.set(AppState::Settings);
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+fn click_editor_button(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<NextState<AppState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<EditorButton>),
+    >,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                state.set(AppState::Editor);
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
 fn cleanup_menu(
     mut commands: Commands,
-    button: Query<Entity, With<Button>>,
+    root: Query<Entity, With<MenuRoot>>,
     camera: Query<Entity, With<MenuCamera>>,
 ) {
-    commands.entity(button.single()).despawn_recursive();
+    commands.entity(root.single()).despawn_recursive();
     commands.entity(camera.single()).despawn_recursive();
 }
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{prelude::*, core_pipeline::clear_color::ClearColorConfig, render::{camera::{ScalingMode, Viewport}, view::RenderLayers}, window::PrimaryWindow};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{AppState, actions::{Action, Bindings, BoundActions}, camera::Focus, photo::in_photo_mode};
+use crate::game::gameplay_elements::{ball::GolfBall, death_zone::DeathZone, goal::Goal, launcher::Launcher, wall::{BounceWall, LowGravWall}};
+
+/// Corner map seen from above, icons and hazard colours are only drawn on its render layer
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MinimapSettings>()
+            .add_system(setup_minimap.in_schedule(OnEnter(AppState::Playing)))
+            .add_system(control_minimap
+                .in_set(OnUpdate(AppState::Playing))
+                .run_if(not(in_photo_mode))
+            )
+            .add_system(update_minimap_camera
+                .in_set(OnUpdate(AppState::Playing))
+                .after(control_minimap)
+            )
+            .add_system(spawn_icons.in_set(OnUpdate(AppState::Playing)))
+            .add_system(follow_icons
+                .in_set(OnUpdate(AppState::Playing))
+                .after(spawn_icons)
+                .after(update_minimap_camera)
+            )
+            .add_system(add_hazard_overlays.in_set(OnUpdate(AppState::Playing)))
+            .add_system(update_shot_trail
+                .in_set(OnUpdate(AppState::Playing))
+                .after(update_minimap_camera)
+            )
+            .add_system(cleanup_minimap.in_schedule(OnExit(AppState::Playing)))
+            ;
+    }
+}
+
+#[derive(Resource)]
+pub struct MinimapSettings {
+    pub visible: bool,
+    /// One of `ZOOM_LEVELS`, higher shows less of the level
+    pub zoom: f32,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self { visible: true, zoom: ZOOM_LEVELS[0] }
+    }
+}
+
+impl MinimapSettings {
+    /// The zoom level after the current one, back to the first after the last
+    fn next_zoom(&self) -> f32 {
+        let next = ZOOM_LEVELS.iter().position(|zoom| *zoom == self.zoom).map_or(0, |i| (i + 1) % ZOOM_LEVELS.len());
+        ZOOM_LEVELS[next]
+    }
+}
+
+const ZOOM_LEVELS: [f32; 3] = [1.0, 2.0, 4.0];
+/// World units seen top to bottom at zoom 1
+const MINIMAP_SPAN: f32 = 160.0;
+/// Fraction of the shorter window side
+const MINIMAP_SIZE: f32 = 0.28;
+const MINIMAP_MARGIN_PX: f32 = 20.0;
+/// Height of the minimap camera above its focus
+const MINIMAP_HEIGHT: f32 = 300.0;
+/// Icons float above the level so walls don't hide them
+const ICON_HEIGHT: f32 = 40.0;
+/// Icon radius as a fraction of the visible span, so icons keep their size when zooming
+const ICON_SIZE: f32 = 0.02;
+const TRAIL_DOTS: usize = 200;
+/// Distance the ball travels between two trail dots
+const TRAIL_SPACING: f32 = 1.5;
+
+const MINIMAP_LAYER: u8 = 1;
+
+#[derive(Component)]
+struct MinimapCamera;
+
+/// Disc drawn over `target` on the minimap
+#[derive(Component)]
+struct MinimapIcon {
+    target: Entity,
+}
+
+/// Tinted copy of a hazard's mesh, only seen by the minimap
+#[derive(Component)]
+struct HazardOverlay;
+
+#[derive(Component)]
+struct TrailDot;
+
+fn icon_material(materials: &mut Assets<StandardMaterial>, color: Color) -> Handle<StandardMaterial> {
+    materials.add(StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..default()
+    })
+}
+
+fn disc_mesh(meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+    meshes.add(Mesh::from(shape::Circle::new(1.0)))
+}
+
+fn setup_minimap(
+    mut commands: Commands,
+    bindings: Res<Bindings>,
+    settings: Res<MinimapSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let bound_actions = BoundActions(vec![Action::ToggleMinimap, Action::MinimapZoom]);
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                // Drawn over the main camera, which has the HDR target
+                order: 1,
+                hdr: true,
+                is_active: settings.visible,
+                ..default()
+            },
+            camera_3d: Camera3d {
+                clear_color: ClearColorConfig::Custom(Color::rgb(0.1, 0.1, 0.12)),
+                ..default()
+            },
+            projection: OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical(MINIMAP_SPAN),
+                scale: 1.0 / settings.zoom,
+                ..default()
+            }.into(),
+            transform: Transform::from_xyz(0., MINIMAP_HEIGHT, 0.).looking_at(Vec3::ZERO, Vec3::NEG_Z),
+            ..default()
+        },
+        RenderLayers::from_layers(&[0, MINIMAP_LAYER]),
+        UiCameraConfig { show_ui: false },
+        bound_actions.input_manager_bundle(&bindings),
+        bound_actions,
+        MinimapCamera,
+        Name::new("Minimap camera"),
+    ));
+
+    let mesh = disc_mesh(&mut meshes);
+    let material = icon_material(&mut materials, Color::rgb(0.9, 0.9, 0.3));
+    for _ in 0..TRAIL_DOTS {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            RenderLayers::layer(MINIMAP_LAYER),
+            TrailDot,
+            Name::new("Trail dot"),
+        ));
+    }
+}
+
+fn control_minimap(
+    mut settings: ResMut<MinimapSettings>,
+    q_camera: Query<&ActionState<Action>, With<MinimapCamera>>,
+) {
+    let Ok(action_state) = q_camera.get_single() else { return };
+    if action_state.just_pressed(Action::ToggleMinimap) {
+        settings.visible = !settings.visible;
+    }
+    if action_state.just_pressed(Action::MinimapZoom) {
+        settings.zoom = settings.next_zoom();
+    }
+}
+
+// Top right corner, centered on the ball or launcher
+fn update_minimap_camera(
+    settings: Res<MinimapSettings>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_focus: Query<&GlobalTransform, With<Focus>>,
+    mut q_camera: Query<(&mut Camera, &mut Projection, &mut Transform), With<MinimapCamera>>,
+) {
+    let (Ok(window), Ok((mut camera, mut projection, mut trans))) = (q_window.get_single(), q_camera.get_single_mut()) else { return };
+
+    camera.is_active = settings.visible;
+    if let Projection::Orthographic(ortho) = &mut *projection {
+        ortho.scale = 1.0 / settings.zoom;
+    }
+
+    let (width, height) = (window.physical_width(), window.physical_height());
+    let size = (width.min(height) as f32 * MINIMAP_SIZE) as u32;
+    let margin = (MINIMAP_MARGIN_PX * window.scale_factor() as f32) as u32;
+    if size == 0 || width < size + margin {
+        camera.is_active = false;
+        return;
+    }
+    camera.viewport = Some(Viewport {
+        physical_position: UVec2::new(width - size - margin, margin),
+        physical_size: UVec2::splat(size),
+        ..default()
+    });
+
+    if let Some(focus) = q_focus.iter().next() {
+        let focus = focus.translation();
+        trans.translation = Vec3::new(focus.x, focus.y + MINIMAP_HEIGHT, focus.z);
+    }
+}
+
+fn icon_scale(settings: &MinimapSettings) -> Vec3 {
+    Vec3::splat(MINIMAP_SPAN / settings.zoom * ICON_SIZE)
+}
+
+fn spawn_icons(
+    mut commands: Commands,
+    q_ball: Query<Entity, Added<GolfBall>>,
+    q_launcher: Query<Entity, Added<Launcher>>,
+    q_goal: Query<Entity, Added<Goal>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let icons = q_ball.iter().map(|entity| (entity, Color::WHITE))
+        .chain(q_launcher.iter().map(|entity| (entity, Color::rgb(0.8, 0.7, 0.6))))
+        .chain(q_goal.iter().map(|entity| (entity, Color::rgb(0.1, 0.9, 0.1))));
+    for (target, color) in icons {
+        commands.spawn((
+            PbrBundle {
+                mesh: disc_mesh(&mut meshes),
+                material: icon_material(&mut materials, color),
+                ..default()
+            },
+            RenderLayers::layer(MINIMAP_LAYER),
+            MinimapIcon { target },
+            Name::new("Minimap icon"),
+        ));
+    }
+}
+
+// Icons go away with their target, the launcher and ball are respawned every shot
+fn follow_icons(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    q_target: Query<&GlobalTransform, Without<MinimapIcon>>,
+    mut q_icon: Query<(Entity, &MinimapIcon, &mut Transform)>,
+) {
+    for (entity, icon, mut trans) in q_icon.iter_mut() {
+        let Ok(target) = q_target.get(icon.target) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        *trans = Transform::from_translation(target.translation() + Vec3::Y * ICON_HEIGHT)
+            .with_rotation(Quat::from_rotation_x(-FRAC_PI_2))
+            .with_scale(icon_scale(&settings));
+    }
+}
+
+// Hazards get a slightly larger tinted copy of their mesh, redone whenever the mesh changes
+// as walls can change type along with it
+fn add_hazard_overlays(
+    mut commands: Commands,
+    q_hazard: Query<
+        (Entity, &Handle<Mesh>, Option<&Children>, Option<&DeathZone>, Option<&BounceWall>, Option<&LowGravWall>),
+        Changed<Handle<Mesh>>,
+    >,
+    q_overlay: Query<(), With<HazardOverlay>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, mesh, children, death_zone, bounce_wall, low_grav_wall) in q_hazard.iter() {
+        for child in children.iter().flat_map(|children| children.iter()) {
+            if q_overlay.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+        let color = match (death_zone, bounce_wall, low_grav_wall) {
+            (Some(_), _, _) => Color::rgb(0.9, 0.15, 0.15),
+            (_, Some(_), _) => Color::rgb(0.95, 0.85, 0.2),
+            (_, _, Some(_)) => Color::rgb(0.25, 0.45, 0.95),
+            _ => continue,
+        };
+        let overlay = commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: icon_material(&mut materials, color),
+                transform: Transform::from_scale(Vec3::splat(1.02)),
+                ..default()
+            },
+            RenderLayers::layer(MINIMAP_LAYER),
+            HazardOverlay,
+            Name::new("Minimap overlay"),
+        )).id();
+        commands.entity(entity).add_child(overlay);
+    }
+}
+
+/// Where the last ball went, kept until the next shot
+#[derive(Default)]
+struct ShotTrail {
+    ball: Option<Entity>,
+    points: VecDeque<Vec3>,
+}
+
+impl ShotTrail {
+    /// Adds a dot once the ball is `TRAIL_SPACING` from the last one, dropping the oldest past `TRAIL_DOTS`
+    fn push(&mut self, position: Vec3) {
+        if self.points.back().map_or(true, |last| last.distance(position) >= TRAIL_SPACING) {
+            self.points.push_back(position);
+            if self.points.len() > TRAIL_DOTS {
+                self.points.pop_front();
+            }
+        }
+    }
+}
+
+fn update_shot_trail(
+    settings: Res<MinimapSettings>,
+    q_ball: Query<(Entity, &GlobalTransform), With<GolfBall>>,
+    mut q_dots: Query<(&mut Transform, &mut Visibility), With<TrailDot>>,
+    mut trail: Local<ShotTrail>,
+) {
+    if let Ok((ball, ball_trans)) = q_ball.get_single() {
+        if trail.ball != Some(ball) {
+            *trail = ShotTrail { ball: Some(ball), ..default() };
+        }
+        trail.push(ball_trans.translation());
+    }
+
+    // A bit below the icons and half their size
+    let scale = icon_scale(&settings) / 2.0;
+    let mut points = trail.points.iter();
+    for (mut trans, mut visibility) in q_dots.iter_mut() {
+        match points.next() {
+            Some(point) => {
+                *trans = Transform::from_translation(*point + Vec3::Y * (ICON_HEIGHT - 1.0))
+                    .with_rotation(Quat::from_rotation_x(-FRAC_PI_2))
+                    .with_scale(scale);
+                *visibility = Visibility::Visible;
+            },
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+fn cleanup_minimap(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<MinimapCamera>, With<MinimapIcon>, With<TrailDot>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_cycles_through_levels() {
+        let mut settings = MinimapSettings::default();
+        for zoom in ZOOM_LEVELS.iter().skip(1).chain(ZOOM_LEVELS.iter()) {
+            settings.zoom = settings.next_zoom();
+            assert_eq!(settings.zoom, *zoom);
+        }
+    }
+
+    #[test]
+    fn unknown_zoom_goes_back_to_the_first() {
+        let settings = MinimapSettings { zoom: 3.0, ..default() };
+        assert_eq!(settings.next_zoom(), ZOOM_LEVELS[0]);
+    }
+
+    #[test]
+    fn trail_spaces_and_caps_dots() {
+        let mut trail = ShotTrail::default();
+        trail.push(Vec3::ZERO);
+        trail.push(Vec3::X * TRAIL_SPACING / 2.0);
+        assert_eq!(trail.points.len(), 1);
+
+        for i in 1..=TRAIL_DOTS {
+            trail.push(Vec3::X * TRAIL_SPACING * i as f32);
+        }
+        assert_eq!(trail.points.len(), TRAIL_DOTS);
+        assert_eq!(trail.points.front(), Some(&(Vec3::X * TRAIL_SPACING)));
+    }
+}
@@ -0,0 +1,478 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+use std::{num::NonZeroU32, path::PathBuf, sync::{Arc, mpsc::{channel, Receiver, Sender}, Mutex}};
+
+use bevy::{prelude::*, core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping}, window::PrimaryWindow};
+use bevy::render::{
+    RenderApp, RenderSet,
+    camera::RenderTarget,
+    extract_resource::{ExtractResource, ExtractResourcePlugin},
+    render_asset::RenderAssets,
+    render_resource::*,
+    renderer::{RenderDevice, RenderQueue},
+    view::ColorGrading,
+};
+use leafwing_input_manager::prelude::ActionState;
+use wgpu::Maintain;
+
+use crate::{AppState, actions::Action, game::level::intro_playing, loading::FontAssets, minimap::MinimapSettings};
+use crate::camera::{CameraMode, MainCamera, switch_camera_mode};
+use crate::player::InputState as FlyInput;
+
+/// Pauses the game and frees the camera for screenshots, saved to `PHOTO_DIR`
+pub struct PhotoPlugin;
+
+impl Plugin for PhotoPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+        app
+            .init_resource::<PhotoSettings>()
+            .init_resource::<PendingPhoto>()
+            .insert_resource(PhotoReceiver(Mutex::new(receiver)))
+            .add_plugin(ExtractResourcePlugin::<PendingPhoto>::default())
+            .add_system(toggle_photo_mode
+                .in_set(OnUpdate(AppState::Playing))
+                .run_if(not(intro_playing))
+            )
+            .add_system(adjust_photo
+                .in_set(OnUpdate(AppState::Playing))
+                .after(toggle_photo_mode)
+                .run_if(in_photo_mode)
+            )
+            .add_system(take_photo
+                .in_set(OnUpdate(AppState::Playing))
+                .after(adjust_photo)
+                .run_if(in_photo_mode)
+            )
+            .add_system(request_photo_copy)
+            .add_system(save_photos)
+            ;
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(PhotoSender(Mutex::new(sender)))
+                .add_system(copy_photo.in_set(RenderSet::Cleanup));
+        }
+    }
+}
+
+/// Photos are saved here, next to the executable's working directory
+pub const PHOTO_DIR: &str = "pictures";
+
+#[derive(Resource)]
+pub struct PhotoSettings {
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    /// Degrees, positive tilts the view clockwise
+    pub roll: f32,
+    /// Exposure in stops, 0 is unchanged
+    pub exposure: f32,
+    pub bloom: bool,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self { fov: 45.0, roll: 0.0, exposure: 0.0, bloom: true }
+    }
+}
+
+const FOV_RANGE: (f32, f32) = (10.0, 120.0);
+/// Degrees per second for zoom and roll
+const LENS_SPEED: f32 = 30.0;
+/// Stops per second
+const EXPOSURE_SPEED: f32 = 1.0;
+const MAX_EXPOSURE: f32 = 4.0;
+
+/// On the main camera while in photo mode, with what to put back afterwards
+#[derive(Component)]
+struct PhotoMode {
+    mode: CameraMode,
+    transform: Transform,
+    projection: Projection,
+    color_grading: ColorGrading,
+    bloom: Option<BloomSettings>,
+    /// HUD roots hidden for the photo, with their visibility before
+    hidden: Vec<(Entity, Visibility)>,
+    minimap: bool,
+}
+
+#[derive(Component)]
+struct PhotoPanel;
+
+/// Run condition, true while the game is paused for photos
+pub fn in_photo_mode(query: Query<(), With<PhotoMode>>) -> bool {
+    !query.is_empty()
+}
+
+fn toggle_photo_mode(
+    mut commands: Commands,
+    mut q_camera: Query<(Entity, &mut Transform, &mut CameraMode, &ActionState<Action>, &Projection, &ColorGrading, Option<&BloomSettings>, Option<&PhotoMode>), With<MainCamera>>,
+    mut q_hud: Query<(Entity, &mut Visibility), (With<Node>, Without<Parent>)>,
+    q_panel: Query<Entity, With<PhotoPanel>>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut fly_input: ResMut<FlyInput>,
+    mut time: ResMut<Time>,
+    mut settings: ResMut<PhotoSettings>,
+    mut minimap: ResMut<MinimapSettings>,
+    fonts: Res<FontAssets>,
+) {
+    let Ok((camera, mut transform, mut mode, action_state, projection, color_grading, bloom, photo)) = q_camera.get_single_mut() else { return };
+    if !action_state.just_pressed(Action::PhotoMode) {
+        return;
+    }
+
+    let Some(photo) = photo else {
+        info!("Photo mode");
+        time.set_relative_speed(0.0);
+        let hidden = q_hud.iter_mut()
+            .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+            .map(|(entity, mut visibility)| {
+                let previous = *visibility;
+                *visibility = Visibility::Hidden;
+                (entity, previous)
+            })
+            .collect();
+        commands.entity(camera).insert(PhotoMode {
+            mode: *mode,
+            transform: *transform,
+            projection: projection.clone(),
+            color_grading: *color_grading,
+            bloom: bloom.cloned(),
+            hidden,
+            minimap: minimap.visible,
+        });
+        minimap.visible = false;
+        *settings = PhotoSettings {
+            fov: match projection {
+                Projection::Perspective(perspective) => perspective.fov.to_degrees(),
+                Projection::Orthographic(_) => PhotoSettings::default().fov,
+            },
+            exposure: color_grading.exposure,
+            bloom: bloom.is_some(),
+            ..default()
+        };
+        switch_camera_mode(&mut commands, camera, &transform, &mut mode, CameraMode::FreeFly, q_window.get_single_mut().ok(), &mut fly_input);
+        if *mode == CameraMode::FreeFly {
+            fly_input.look_from(&transform);
+        }
+        spawn_photo_panel(&mut commands, &fonts);
+        return;
+    };
+
+    info!("Photo mode off");
+    time.set_relative_speed(1.0);
+    for (entity, visibility) in photo.hidden.iter() {
+        if let Ok((_, mut hud_visibility)) = q_hud.get_mut(*entity) {
+            *hud_visibility = *visibility;
+        }
+    }
+    minimap.visible = photo.minimap;
+    fly_input.roll = 0.0;
+    let mut camera_commands = commands.entity(camera);
+    camera_commands
+        .insert((photo.projection.clone(), photo.color_grading))
+        .remove::<PhotoMode>();
+    match photo.bloom.clone() {
+        Some(bloom) => camera_commands.insert(bloom),
+        None => camera_commands.remove::<BloomSettings>(),
+    };
+    if photo.mode == CameraMode::FreeFly {
+        // Keep flying from where photo mode started
+        *transform = photo.transform;
+        fly_input.look_from(&transform);
+    }
+    switch_camera_mode(&mut commands, camera, &transform, &mut mode, photo.mode, q_window.get_single_mut().ok(), &mut fly_input);
+    for panel in q_panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+fn spawn_photo_panel(commands: &mut Commands, fonts: &FontAssets) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 24.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        }),
+        PhotoPanel,
+        Name::new("Photo panel"),
+    ));
+}
+
+// Game time is paused, so everything here runs on real time
+fn adjust_photo(
+    mut commands: Commands,
+    mut settings: ResMut<PhotoSettings>,
+    mut fly_input: ResMut<FlyInput>,
+    mut q_camera: Query<(Entity, &ActionState<Action>, &mut Projection, &mut ColorGrading), With<MainCamera>>,
+    mut q_panel: Query<&mut Text, With<PhotoPanel>>,
+    time: Res<Time>,
+) {
+    let Ok((camera, action_state, mut projection, mut color_grading)) = q_camera.get_single_mut() else { return };
+    let dt = time.raw_delta_seconds();
+
+    let lens = action_state.clamped_axis_pair(Action::PhotoLens).map_or(Vec2::ZERO, |axis_pair| axis_pair.xy());
+    settings.fov = (settings.fov - lens.y * LENS_SPEED * dt).clamp(FOV_RANGE.0, FOV_RANGE.1);
+    settings.roll = (settings.roll - lens.x * LENS_SPEED * dt).clamp(-180.0, 180.0);
+    if action_state.pressed(Action::PhotoBrighter) {
+        settings.exposure += EXPOSURE_SPEED * dt;
+    }
+    if action_state.pressed(Action::PhotoDarker) {
+        settings.exposure -= EXPOSURE_SPEED * dt;
+    }
+    settings.exposure = settings.exposure.clamp(-MAX_EXPOSURE, MAX_EXPOSURE);
+    if action_state.just_pressed(Action::PhotoBloom) {
+        settings.bloom = !settings.bloom;
+        if settings.bloom {
+            commands.entity(camera).insert(BloomSettings::default());
+        } else {
+            commands.entity(camera).remove::<BloomSettings>();
+        }
+    }
+
+    if let Projection::Perspective(perspective) = &mut *projection {
+        perspective.fov = settings.fov.to_radians();
+    }
+    color_grading.exposure = settings.exposure;
+    fly_input.roll = settings.roll.to_radians();
+
+    for mut text in q_panel.iter_mut() {
+        text.sections[0].value = format!(
+            "Photo mode\nFOV {:.0}°  Roll {:.0}°  Exposure {:+.1}  Bloom {}",
+            settings.fov, settings.roll, settings.exposure, if settings.bloom { "on" } else { "off" },
+        );
+    }
+}
+
+/// Image waiting to be copied back from the GPU by the render world
+#[derive(Resource, Clone, Default, ExtractResource)]
+struct PendingPhoto(Option<(Handle<Image>, PathBuf)>);
+
+/// Renders the main camera's view into `image` for one photo
+#[derive(Component)]
+struct PhotoCamera {
+    image: Handle<Image>,
+    path: PathBuf,
+    frames: u32,
+}
+
+#[derive(Resource)]
+struct PhotoSender(Mutex<Sender<(PathBuf, u32, u32, Vec<u8>)>>);
+
+#[derive(Resource)]
+struct PhotoReceiver(Mutex<Receiver<(PathBuf, u32, u32, Vec<u8>)>>);
+
+fn photo_path() -> PathBuf {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+    PathBuf::from(PHOTO_DIR).join(format!("ggolf_{millis}.png"))
+}
+
+// The window can't be read back, so the same view is rendered again into an image without the UI
+fn take_photo(
+    mut commands: Commands,
+    q_camera: Query<(&ActionState<Action>, &GlobalTransform, &Projection, &ColorGrading, &Tonemapping, Option<&BloomSettings>), With<MainCamera>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_photo: Query<(), With<PhotoCamera>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let (Ok((action_state, global, projection, color_grading, tonemapping, bloom)), Ok(window)) = (q_camera.get_single(), q_window.get_single()) else { return };
+    if !action_state.just_pressed(Action::TakePhoto) || !q_photo.is_empty() {
+        return;
+    }
+
+    let size = Extent3d {
+        width: window.physical_width(),
+        height: window.physical_height(),
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("photo"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    let mut photo_camera = commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(image.clone()),
+                order: -1,
+                hdr: true,
+                ..default()
+            },
+            transform: global.compute_transform(),
+            projection: projection.clone(),
+            color_grading: *color_grading,
+            tonemapping: *tonemapping,
+            ..default()
+        },
+        UiCameraConfig { show_ui: false },
+        PhotoCamera { image, path: photo_path(), frames: 0 },
+        Name::new("Photo camera"),
+    ));
+    if let Some(bloom) = bloom {
+        photo_camera.insert(bloom.clone());
+    }
+}
+
+// Copied once the photo camera has rendered a frame
+fn request_photo_copy(
+    mut q_photo: Query<&mut PhotoCamera>,
+    mut pending: ResMut<PendingPhoto>,
+) {
+    for mut photo in q_photo.iter_mut() {
+        photo.frames += 1;
+        if photo.frames == 2 {
+            pending.0 = Some((photo.image.clone(), photo.path.clone()));
+        }
+    }
+}
+
+/// Photo copied into `buffer`, waiting for the GPU to map it
+struct Readback {
+    path: PathBuf,
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_row_bytes: u32,
+    /// Set once mapping finished, false if it failed
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+#[derive(Default)]
+struct PhotoCopy {
+    readback: Option<Readback>,
+    /// The last photo copied, the pending photo stays set until the main world gets it
+    copied: Option<PathBuf>,
+}
+
+// Runs in the render world after rendering, the copy is read back on a later frame once the GPU is done with it
+fn copy_photo(
+    pending: Res<PendingPhoto>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    sender: Res<PhotoSender>,
+    mut copy: Local<PhotoCopy>,
+) {
+    if let Some(readback) = copy.readback.take() {
+        render_device.wgpu_device().poll(Maintain::Poll);
+        let mapped = readback.mapped.lock().ok().and_then(|mapped| *mapped);
+        match mapped {
+            None => copy.readback = Some(readback),
+            Some(false) => {
+                // Copied again next frame
+                error!("Could not read back photo {}", readback.path.display());
+                copy.copied = None;
+            },
+            Some(true) => {
+                // Buffer rows are padded, the padding is dropped again here
+                let row_bytes = (readback.width * 4) as usize;
+                let pixels: Vec<u8> = readback.buffer.slice(..).get_mapped_range()
+                    .chunks(readback.padded_row_bytes as usize)
+                    .flat_map(|row| &row[..row_bytes])
+                    .copied()
+                    .collect();
+                readback.buffer.unmap();
+                if let Ok(sender) = sender.0.lock() {
+                    let _ = sender.send((readback.path, readback.width, readback.height, pixels));
+                }
+            },
+        }
+        return;
+    }
+
+    let Some((image, path)) = &pending.0 else { return };
+    if copy.copied.as_ref() == Some(path) {
+        return;
+    }
+    let Some(gpu_image) = images.get(image) else { return };
+
+    let (width, height) = (gpu_image.size.x as u32, gpu_image.size.y as u32);
+    // Buffer rows must be aligned
+    let row_bytes = width * 4;
+    let padded_row_bytes = row_bytes + (COPY_BYTES_PER_ROW_ALIGNMENT - row_bytes % COPY_BYTES_PER_ROW_ALIGNMENT) % COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("photo buffer"),
+        size: (padded_row_bytes * height) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        gpu_image.texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                rows_per_image: None,
+            },
+        },
+        Extent3d { width, height, depth_or_array_layers: 1 },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let mapped = Arc::new(Mutex::new(None));
+    let on_mapped = mapped.clone();
+    buffer.slice(..).map_async(MapMode::Read, move |result| {
+        if let Ok(mut mapped) = on_mapped.lock() {
+            *mapped = Some(result.is_ok());
+        }
+    });
+    copy.copied = Some(path.clone());
+    copy.readback = Some(Readback { path: path.clone(), buffer, width, height, padded_row_bytes, mapped });
+}
+
+fn save_photos(
+    mut commands: Commands,
+    receiver: Res<PhotoReceiver>,
+    mut pending: ResMut<PendingPhoto>,
+    q_photo: Query<(Entity, &PhotoCamera)>,
+) {
+    let Ok(receiver) = receiver.0.lock() else { return };
+    for (path, width, height, pixels) in receiver.try_iter() {
+        for (entity, photo) in q_photo.iter() {
+            if photo.path == path {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        pending.0 = None;
+
+        // This can't work in WASM as there is no filesystem access
+        #[cfg(not(target_arch = "wasm32"))]
+        bevy::tasks::IoTaskPool::get()
+            .spawn(async move {
+                let Some(image) = image::RgbaImage::from_raw(width, height, pixels) else { return };
+                let saved = std::fs::create_dir_all(PHOTO_DIR)
+                    .map_err(|err| err.to_string())
+                    .and_then(|_| image.save(&path).map_err(|err| err.to_string()));
+                match saved {
+                    Ok(()) => info!("Saved photo {}", path.display()),
+                    Err(err) => error!("Could not save photo: {err}"),
+                }
+            })
+            .detach();
+    }
+}
@@ -35,10 +35,22 @@ fn setup(
 
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource, Default)]
-struct InputState {
+pub(crate) struct InputState {
     reader_motion: ManualEventReader<MouseMotion>,
     pitch: f32,
     yaw: f32,
+    /// Tilt around the view direction, only set by photo mode
+    pub(crate) roll: f32,
+}
+
+impl InputState {
+    /// Continue looking from `transform` instead of jumping back to the last fly look
+    pub(crate) fn look_from(&mut self, transform: &Transform) {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-1.54, 1.54);
+        self.roll = 0.0;
+    }
 }
 /// Mouse sensitivity and movement speed
 #[derive(Resource)]
@@ -112,7 +124,8 @@ fn player_move(
 
         velocity = velocity.normalize_or_zero();
 
-        transform.translation += velocity * time.delta_seconds() * settings.speed
+        // Real time, so flying still works while photo mode pauses the game
+        transform.translation += velocity * time.raw_delta_seconds() * settings.speed
     }
 }
 
@@ -144,18 +157,22 @@ fn player_look(
             }
 
             delta_state.pitch = delta_state.pitch.clamp(-1.54, 1.54);
-
-            // Order is important to prevent unintended roll
-            transform.rotation = Quat::from_axis_angle(Vec3::Y, delta_state.yaw)
-                * Quat::from_axis_angle(Vec3::X, delta_state.pitch);
         }
+
+        // Order is important to prevent unintended roll
+        transform.rotation = Quat::from_axis_angle(Vec3::Y, delta_state.yaw)
+            * Quat::from_axis_angle(Vec3::X, delta_state.pitch)
+            * Quat::from_axis_angle(Vec3::Z, delta_state.roll);
     }
 }
 
 fn cursor_grab(
     keys: Res<Input<KeyCode>>,
     mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
+    query: Query<(), With<FlyCam>>,
 ) {
+    if query.is_empty() { return; }
+
     let Ok(mut window) = primary_query.get_single_mut() else {
         warn!("Primary window not found for `cursor_grab`!");
         return;
@@ -0,0 +1,366 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+use crate::actions::{Action, Binding, Bindings, Device};
+use crate::loading::FontAssets;
+use crate::menu::ButtonColors;
+use crate::AppState;
+use bevy::prelude::*;
+use leafwing_input_manager::{prelude::*, user_input::InputKind};
+
+/// Control rebinding screen, reached from the main menu
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+            .add_system(setup_settings.in_schedule(OnEnter(AppState::Settings)))
+            .add_systems(
+                (click_settings_buttons, capture_binding, update_binding_labels)
+                    .chain()
+                    .in_set(OnUpdate(AppState::Settings)),
+            )
+            .add_system(cleanup_settings.in_schedule(OnExit(AppState::Settings)));
+    }
+}
+
+/// Binding waiting for the player to press its inputs
+struct Capture {
+    action: Action,
+    device: Device,
+    /// Buttons pressed so far for a four button axis
+    pressed: Vec<InputKind>,
+    /// The click that started the capture must be let go first
+    waiting_release: bool,
+}
+
+#[derive(Resource, Default)]
+struct Rebinding(Option<Capture>);
+
+#[derive(Component)]
+struct SettingsRoot;
+
+#[derive(Component)]
+struct SettingsCamera;
+
+#[derive(Component, Clone, Copy)]
+enum SettingsButton {
+    Rebind(Action, Device),
+    InvertX,
+    InvertY,
+    Defaults,
+    Back,
+}
+
+#[derive(Component)]
+struct ConflictText;
+
+const DIRECTIONS: [&str; 4] = ["up", "down", "left", "right"];
+
+fn invert_label(axis: &str, inverted: bool) -> String {
+    format!("Invert camera {axis}: {}", if inverted { "On" } else { "Off" })
+}
+
+fn setup_settings(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    bindings: Res<Bindings>,
+) {
+    commands.spawn((Camera2dBundle::default(), SettingsCamera));
+    let text_style = TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size: 24.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let button = |width: f32| ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Px(width), Val::Px(36.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: button_colors.normal.into(),
+        ..Default::default()
+    };
+    let row = || NodeBundle {
+        style: Style {
+            align_items: AlignItems::Center,
+            gap: Size::all(Val::Px(10.0)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let cell = |width: f32| NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(width), Val::Px(36.0)),
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::all(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            SettingsRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn(row()).with_children(|parent| {
+                for (label, width) in [("Action", 180.0), ("Keyboard and mouse", 260.0), ("Gamepad", 260.0)] {
+                    parent.spawn(cell(width)).with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                    });
+                }
+            });
+            for action in Action::variants() {
+                parent.spawn(row()).with_children(|parent| {
+                    parent.spawn(cell(180.0)).with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(action.label(), text_style.clone()));
+                    });
+                    for device in [Device::KeyboardMouse, Device::Gamepad] {
+                        parent
+                            .spawn((button(260.0), SettingsButton::Rebind(action, device)))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("", text_style.clone()));
+                            });
+                    }
+                });
+            }
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        color: Color::rgb(0.9, 0.3, 0.3),
+                        ..text_style.clone()
+                    },
+                ),
+                ConflictText,
+            ));
+            parent.spawn(row()).with_children(|parent| {
+                let inverts = [
+                    (invert_label("X", bindings.invert_x), SettingsButton::InvertX),
+                    (invert_label("Y", bindings.invert_y), SettingsButton::InvertY),
+                ];
+                for (label, settings_button) in inverts {
+                    parent
+                        .spawn((button(260.0), settings_button))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                        });
+                }
+            });
+            parent.spawn(row()).with_children(|parent| {
+                for (label, settings_button) in [("Defaults", SettingsButton::Defaults), ("Back", SettingsButton::Back)] {
+                    parent
+                        .spawn((button(140.0), settings_button))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                        });
+                }
+            });
+        });
+}
+
+fn click_settings_buttons(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<NextState<AppState>>,
+    mut bindings: ResMut<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &SettingsButton, &Children),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, mut color, settings_button, children) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => match *settings_button {
+                SettingsButton::Rebind(action, device) => {
+                    rebinding.0 = Some(Capture {
+                        action,
+                        device,
+                        pressed: Vec::new(),
+                        waiting_release: true,
+                    });
+                }
+                SettingsButton::InvertX | SettingsButton::InvertY => {
+                    let label = if let SettingsButton::InvertX = settings_button {
+                        bindings.invert_x = !bindings.invert_x;
+                        invert_label("X", bindings.invert_x)
+                    } else {
+                        bindings.invert_y = !bindings.invert_y;
+                        invert_label("Y", bindings.invert_y)
+                    };
+                    for child in children.iter() {
+                        if let Ok(mut text) = text_query.get_mut(*child) {
+                            text.sections[0].value = label.clone();
+                        }
+                    }
+                }
+                SettingsButton::Defaults => {
+                    rebinding.0 = None;
+                    // Only the inputs, the invert buttons keep showing what they're set to
+                    *bindings = Bindings {
+                        invert_x: bindings.invert_x,
+                        invert_y: bindings.invert_y,
+                        ..Bindings::default()
+                    };
+                }
+                SettingsButton::Back => {
+                    state.set(AppState::Menu);
+                }
+            },
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+// Keyboard and mouse buttons, or gamepad buttons and sticks, depending on the column, Escape cancels
+fn capture_binding(
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<Bindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    q_clicked: Query<&Interaction, (Changed<Interaction>, With<SettingsButton>)>,
+) {
+    if rebinding.0.is_none() {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        return;
+    }
+    let Some(capture) = rebinding.0.as_mut() else { return };
+    if capture.waiting_release {
+        capture.waiting_release = mouse.get_pressed().next().is_some();
+        return;
+    }
+
+    // Clicking another button, like Back, isn't a left click to bind
+    let clicked_button = q_clicked.iter().any(|interaction| *interaction == Interaction::Clicked);
+    let pressed = match capture.device {
+        Device::KeyboardMouse => keys
+            .get_just_pressed()
+            .next()
+            .map(|key| InputKind::Keyboard(*key))
+            .or_else(|| mouse
+                .get_just_pressed()
+                .find(|button| !(clicked_button && **button == MouseButton::Left))
+                .map(|button| InputKind::Mouse(*button))),
+        Device::Gamepad => gamepad_buttons
+            .get_just_pressed()
+            .next()
+            .map(|button| InputKind::GamepadButton(button.button_type)),
+    };
+
+    // A whole stick can be bound to an axis action by pushing it
+    let stick = |x: GamepadAxisType, y: GamepadAxisType| {
+        gamepads.iter().any(|gamepad| {
+            let value = |axis_type| gamepad_axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.0);
+            Vec2::new(value(x), value(y)).length() > 0.7
+        })
+    };
+    let binding = if capture.action.is_axis() {
+        if capture.device == Device::Gamepad && capture.pressed.is_empty() && stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY) {
+            Some(Binding::Single(DualAxis::left_stick().into()))
+        } else if capture.device == Device::Gamepad && capture.pressed.is_empty() && stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY) {
+            Some(Binding::Single(DualAxis::right_stick().into()))
+        } else {
+            capture.pressed.extend(pressed);
+            match capture.pressed[..] {
+                [up, down, left, right] => Some(Binding::DPad { up, down, left, right }),
+                _ => None,
+            }
+        }
+    } else {
+        pressed.map(Binding::Single)
+    };
+
+    if let Some(binding) = binding {
+        info!("Bound {:?} to {}", capture.action, binding.label());
+        bindings.set(capture.action, capture.device, binding);
+        rebinding.0 = None;
+    }
+}
+
+fn update_binding_labels(
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    q_added: Query<(), Added<SettingsButton>>,
+    q_buttons: Query<(&SettingsButton, &Children)>,
+    mut q_text: Query<&mut Text, Without<ConflictText>>,
+    mut q_conflicts: Query<&mut Text, With<ConflictText>>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() && q_added.is_empty() {
+        return;
+    }
+
+    for (settings_button, children) in q_buttons.iter() {
+        let SettingsButton::Rebind(action, device) = *settings_button else { continue };
+        let (label, color) = match &rebinding.0 {
+            Some(capture) if capture.action == action && capture.device == device => {
+                let label = if action.is_axis() {
+                    format!("Press {}", DIRECTIONS[capture.pressed.len()])
+                } else {
+                    "Press a button".to_string()
+                };
+                (label, Color::rgb(0.9, 0.9, 0.3))
+            }
+            _ => {
+                let label = bindings.get(action, device).map_or("-".to_string(), |binding| binding.label());
+                let color = if bindings.is_conflicted(action, device) {
+                    Color::rgb(0.9, 0.3, 0.3)
+                } else {
+                    Color::rgb(0.9, 0.9, 0.9)
+                };
+                (label, color)
+            }
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = q_text.get_mut(*child) {
+                text.sections[0].value = label.clone();
+                text.sections[0].style.color = color;
+            }
+        }
+    }
+
+    let conflicts: Vec<String> = bindings
+        .conflicts()
+        .iter()
+        .map(|(a, b, _)| format!("{} and {} share an input", a.label(), b.label()))
+        .chain(bindings.reserved_conflicts().iter().map(|(action, key)| format!("{} uses {key:?}, which is reserved", action.label())))
+        .collect();
+    for mut text in q_conflicts.iter_mut() {
+        text.sections[0].value = conflicts.join("\n");
+    }
+}
+
+fn cleanup_settings(
+    mut commands: Commands,
+    mut rebinding: ResMut<Rebinding>,
+    bindings: Res<Bindings>,
+    root: Query<Entity, With<SettingsRoot>>,
+    camera: Query<Entity, With<SettingsCamera>>,
+) {
+    rebinding.0 = None;
+    bindings.save();
+    commands.entity(root.single()).despawn_recursive();
+    commands.entity(camera.single()).despawn_recursive();
+}
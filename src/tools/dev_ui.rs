@@ -1,7 +1,8 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_inspector_egui::{egui::{self, Visuals}, bevy_egui::EguiContext};
 
-use crate::game::{level::{level_manager::SaveLevelEvent, Level}, gameplay_elements::{launcher::LaunchVelocity, wall::{LowGravWall, BounceWall, PlainWall, Box}}, GameState};
+use crate::camera::CameraSettings;
+use crate::game::{level::{level_manager::{SaveLevelEvent, LoadLevelEvent, LevelFile, LevelFileStatus, level_files}, Level, LevelSettings}, gameplay_elements::{launcher::{LaunchVelocity, ChargeCurve, ControlScheme, MAX_LAUNCH_VELOCITY}, wall::{LowGravWall, BounceWall, PlainWall, Box}}, GameState};
 
 pub struct DevUiPlugin;
 
@@ -16,14 +17,101 @@ impl Plugin for DevUiPlugin {
 
 struct UiState {
     name_input: String,
+    /// Level files on disk, listed again when `None`
+    files: Option<Vec<String>>,
+    confirm: Option<FileAction>,
 }
 
 impl Default for UiState {
     fn default() -> Self {
-        Self { name_input: "level_1".to_string() }
+        Self { name_input: "level_1".to_string(), files: None, confirm: None }
     }
 }
 
+/// Level file actions that ask before they're done, only `New` on the web where there are no files
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FileAction {
+    New,
+    #[cfg(not(target_arch = "wasm32"))]
+    Duplicate,
+    #[cfg(not(target_arch = "wasm32"))]
+    Delete,
+}
+
+impl FileAction {
+    const ALL: &'static [FileAction] = &[
+        FileAction::New,
+        #[cfg(not(target_arch = "wasm32"))]
+        FileAction::Duplicate,
+        #[cfg(not(target_arch = "wasm32"))]
+        FileAction::Delete,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            FileAction::New => "New",
+            #[cfg(not(target_arch = "wasm32"))]
+            FileAction::Duplicate => "Duplicate",
+            #[cfg(not(target_arch = "wasm32"))]
+            FileAction::Delete => "Delete",
+        }
+    }
+
+    fn question(&self, file: &LevelFile) -> String {
+        match self {
+            FileAction::New => "Replace the current level with an empty one?".to_string(),
+            #[cfg(not(target_arch = "wasm32"))]
+            FileAction::Duplicate => format!("Copy {} to {}?", file.stem(), copy_name(file)),
+            #[cfg(not(target_arch = "wasm32"))]
+            FileAction::Delete => format!("Delete {} from disk?", file.stem()),
+        }
+    }
+
+    fn run(&self, world: &mut World, file: &LevelFile) {
+        let result: std::io::Result<()> = match self {
+            FileAction::New => {
+                new_level(world);
+                Ok(())
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            FileAction::Duplicate => std::fs::copy(file.file_path(), LevelFile::Name(copy_name(file)).file_path()).map(|_| ()),
+            #[cfg(not(target_arch = "wasm32"))]
+            FileAction::Delete => std::fs::remove_file(file.file_path()),
+        };
+        let status = match result {
+            Ok(()) => String::new(),
+            Err(error) => {
+                warn!("{} {} failed: {}", self.label(), file.stem(), error);
+                format!("{} {} failed: {}", self.label(), file.stem(), error)
+            }
+        };
+        world.resource::<LevelFileStatus>().set(status);
+    }
+}
+
+// First free name, `level_1_copy`, `level_1_copy_2`...
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_name(file: &LevelFile) -> String {
+    let stem = format!("{}_copy", file.stem());
+    let mut name = stem.clone();
+    let mut n = 1;
+    while std::path::Path::new(&LevelFile::Name(name.clone()).file_path()).exists() {
+        n += 1;
+        name = format!("{stem}_{n}");
+    }
+    name
+}
+
+// Settings are the only child so the level can be saved straight away
+fn new_level(world: &mut World) {
+    let levels: Vec<Entity> = world.query_filtered::<Entity, With<Level>>().iter(world).collect();
+    for level in levels {
+        world.entity_mut(level).despawn_recursive();
+    }
+    let settings = world.spawn(LevelSettings::default()).id();
+    world.spawn((Level, SpatialBundle::default(), Name::new("Level"))).add_child(settings);
+}
+
 fn setup_ui(
     world: &mut World,
     mut state: Local<UiState>,
@@ -40,10 +128,50 @@ fn setup_ui(
         ui.text_edit_singleline(&mut state.name_input);
         ui.horizontal(|ui| {
             if ui.button("Save").clicked() {
-                world.send_event::<SaveLevelEvent>(SaveLevelEvent {name: state.name_input.to_string() });
+                world.send_event::<SaveLevelEvent>(SaveLevelEvent { level: LevelFile::parse(&state.name_input) });
+                state.files = None;
             }
             if ui.button("Load").clicked() {
-                info!("Button 2 clicked!");
+                world.send_event::<LoadLevelEvent>(LoadLevelEvent { level: LevelFile::parse(&state.name_input) });
+            }
+            for action in FileAction::ALL {
+                if ui.button(action.label()).clicked() {
+                    state.confirm = Some(*action);
+                }
+            }
+        });
+        if let Some(action) = state.confirm {
+            let file = LevelFile::parse(&state.name_input);
+            ui.horizontal(|ui| {
+                ui.label(action.question(&file));
+                if ui.button("Yes").clicked() {
+                    action.run(world, &file);
+                    state.confirm = None;
+                    state.files = None;
+                }
+                if ui.button("No").clicked() {
+                    state.confirm = None;
+                }
+            });
+        }
+        let status = world.resource::<LevelFileStatus>().get();
+        if !status.is_empty() {
+            ui.label(status);
+        }
+        ui.collapsing("Level files", |ui| {
+            let files = state.files.get_or_insert_with(level_files).clone();
+            for file in files {
+                // Double click loads
+                let response = ui.selectable_label(state.name_input == file, &file);
+                if response.clicked() {
+                    state.name_input = file.clone();
+                }
+                if response.double_clicked() {
+                    world.send_event::<LoadLevelEvent>(LoadLevelEvent { level: LevelFile::Name(file) });
+                }
+            }
+            if ui.button("Refresh").clicked() {
+                state.files = None;
             }
         });
         ui.label("Level Controls");
@@ -72,19 +200,45 @@ fn setup_ui(
             if ui.button("Bounce Wall").clicked() {
                 info!("Button 2 clicked!");
                 let level_entity = world.query_filtered::<Entity, With<Level>>().single(world);
-                let id = world.spawn(BounceWall).insert((Box{ x: box_dims.x, y: box_dims.y, z: box_dims.z }, SpatialBundle::default())).id();
+                let id = world.spawn(BounceWall::default()).insert((Box{ x: box_dims.x, y: box_dims.y, z: box_dims.z }, SpatialBundle::default())).id();
                 world.entity_mut(level_entity).add_child(id);
             }
             if ui.button("Gravity Wall").clicked() {
                 info!("Button 2 clicked!");
                 let level_entity = world.query_filtered::<Entity, With<Level>>().single(world);
-                let id = world.spawn(LowGravWall).insert((Box{ x: box_dims.x, y: box_dims.y, z: box_dims.z }, SpatialBundle::default())).id();
+                let id = world.spawn(LowGravWall::default()).insert((Box{ x: box_dims.x, y: box_dims.y, z: box_dims.z }, SpatialBundle::default())).id();
                 world.entity_mut(level_entity).add_child(id);
             }
         });
 
         ui.label("Launch velocity:");
-        ui.add(egui::Slider::new(&mut world.resource_mut::<LaunchVelocity>().0, 10.0..=100.0));
+        ui.add(egui::Slider::new(&mut world.resource_mut::<LaunchVelocity>().0, 10.0..=MAX_LAUNCH_VELOCITY));
+        ui.label("Charge curve:");
+        ui.horizontal(|ui| {
+            let mut curve = world.resource_mut::<ChargeCurve>();
+            for option in [ChargeCurve::Linear, ChargeCurve::EaseIn, ChargeCurve::Oscillating] {
+                ui.selectable_value(&mut *curve, option, option.label());
+            }
+        });
+        ui.label("Camera distance:");
+        ui.horizontal(|ui| {
+            let mut camera_settings = world.resource_mut::<CameraSettings>();
+            ui.add(egui::DragValue::new(&mut camera_settings.min_distance).clamp_range(1.0..=100.0));
+            ui.add(egui::DragValue::new(&mut camera_settings.max_distance).clamp_range(1.0..=100.0));
+        });
+        ui.label("Camera stiffness and look-ahead:");
+        ui.horizontal(|ui| {
+            let mut camera_settings = world.resource_mut::<CameraSettings>();
+            ui.add(egui::DragValue::new(&mut camera_settings.follow_stiffness).clamp_range(0.5..=50.0));
+            ui.add(egui::DragValue::new(&mut camera_settings.look_ahead_secs).speed(0.01).clamp_range(0.0..=2.0));
+        });
+        ui.label("Controls:");
+        ui.horizontal(|ui| {
+            let mut scheme = world.resource_mut::<ControlScheme>();
+            for option in [ControlScheme::Buttons, ControlScheme::Slingshot] {
+                ui.selectable_value(&mut *scheme, option, option.label());
+            }
+        });
         
         // let mut value = true;
         // ui.horizontal(|ui| {
@@ -0,0 +1,50 @@
+use bevy::{prelude::*, scene::ScenePlugin};
+use bevy_rapier3d::prelude::*;
+use ggolf::{rapier_configuration, BallPhysicsBundle, BALL_RADIUS, FIXED_TIMESTEP, MAX_LAUNCH_VELOCITY, MAX_CHARGE_SECS};
+
+// Physics only app, the game's fixed timestep makes every update one simulation tick
+//
+// Without substeps a max power shot moves further per tick than the ball and wall are thick,
+// so only CCD keeps it from tunnelling
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Mesh>()
+        .add_plugin(ScenePlugin)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed { dt: FIXED_TIMESTEP, substeps: 1 },
+            ..rapier_configuration()
+        });
+    app
+}
+
+#[test]
+fn max_power_shot_does_not_tunnel_through_thin_wall() {
+    let mut app = headless_app();
+    let wall_z = -20.0;
+    let wall_half_thickness = 0.5;
+
+    // As thin as a `wall::Box` gets
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0., 0., wall_z)),
+        Collider::cuboid(20., 20., wall_half_thickness),
+        RigidBody::Fixed,
+    ));
+    let ball = app.world.spawn((
+        TransformBundle::default(),
+        BallPhysicsBundle::default(),
+        GravityScale(0.0),
+        Velocity::linear(Vec3::NEG_Z * MAX_LAUNCH_VELOCITY * MAX_CHARGE_SECS),
+    )).id();
+
+    for _ in 0..120 {
+        app.update();
+        let z = app.world.get::<Transform>(ball).unwrap().translation.z;
+        // The ball stops against the near face of the wall, give or take contact slop
+        assert!(z > wall_z + wall_half_thickness + BALL_RADIUS - 0.1, "Ball passed into the wall, z = {z}");
+    }
+}
@@ -0,0 +1,61 @@
+use bevy::{prelude::*, scene::ScenePlugin};
+use bevy_rapier3d::prelude::*;
+use ggolf::{rapier_configuration, BallPhysicsBundle, MAX_LAUNCH_VELOCITY};
+
+// Physics only app with the game's configuration, every update is one fixed tick
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Mesh>()
+        .add_plugin(ScenePlugin)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .insert_resource(rapier_configuration());
+    app
+}
+
+// Shoots the ball across the ground into an angled wall and records where it is after every tick
+fn play_shot(ticks: usize) -> Vec<Transform> {
+    let mut app = headless_app();
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0., -1., 0.)),
+        Collider::cuboid(50., 0.5, 50.),
+        RigidBody::Fixed,
+    ));
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0., 2., -15.).with_rotation(Quat::from_rotation_y(0.6))),
+        Collider::cuboid(10., 3., 0.5),
+        RigidBody::Fixed,
+        Restitution::new(0.8),
+    ));
+    let ball = app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0., 1., 0.)),
+        BallPhysicsBundle::default(),
+        Velocity {
+            linvel: Vec3::new(0.1, 0.3, -1.0).normalize() * MAX_LAUNCH_VELOCITY,
+            angvel: Vec3::new(-5.0, 0.0, 2.0),
+        },
+    )).id();
+
+    (0..ticks)
+        .map(|_| {
+            app.update();
+            *app.world.get::<Transform>(ball).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn same_shot_ends_in_the_same_place() {
+    let ticks = 300;
+    let first = play_shot(ticks);
+    let second = play_shot(ticks);
+
+    // The shot has to actually go somewhere for the comparison to mean anything
+    assert!(first[ticks - 1].translation.distance(Vec3::new(0., 1., 0.)) > 1.0);
+    for (tick, (a, b)) in first.iter().zip(second.iter()).enumerate() {
+        assert_eq!(a, b, "Shots diverged at tick {tick}");
+    }
+}